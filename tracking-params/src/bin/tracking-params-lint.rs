@@ -0,0 +1,87 @@
+//! Lints the builtin rules and checks their examples, the urls in the given files
+//! count as tested for [`tracking_params::LintKind::UntestedHandler`]:
+//!
+//! ```text
+//! cargo run --bin tracking-params-lint -- src/lib.rs
+//! ```
+//!
+//! The rules of a JSON file are checked instead with `--rules` (needs the `rule-files`
+//! feature, see [`RuleSet::leak_from_json`]):
+//!
+//! ```text
+//! cargo run --features rule-files --bin tracking-params-lint -- --rules rules.json
+//! ```
+//!
+//! Exits with `1` when there are errors, or warnings with `--deny-warnings`. Intended
+//! warnings are allowed on their rule with [`tracking_params::Rule::allowing_lint`].
+use std::process::ExitCode;
+
+use anyhow::Context;
+use tracking_params::RuleSet;
+
+fn main() -> anyhow::Result<ExitCode> {
+    let mut sources = vec![];
+    let mut deny_warnings = false;
+    let mut rules = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--deny-warnings" => deny_warnings = true,
+            "--rules" => {
+                rules = Some(args.next().context("`--rules` needs a file")?);
+            }
+            path => sources.push(std::fs::read_to_string(path)?),
+        }
+    }
+    let loaded;
+    let set = match rules {
+        Some(path) => {
+            loaded = load_rules(&path)?;
+            &loaded
+        }
+        None => RuleSet::builtin(),
+    };
+    let tested_urls = sources
+        .iter()
+        .flat_map(|source| string_urls(source))
+        .collect::<Vec<_>>();
+
+    let lints = set.lint_with_tests(&tested_urls);
+    for lint in &lints {
+        println!("{lint}");
+    }
+
+    let failures = set.verify().err().unwrap_or_default();
+    for failure in &failures {
+        println!("error{failure}");
+    }
+
+    let errors = lints.iter().filter(|l| l.kind.is_error()).count() + failures.len();
+    let warnings = lints.len() + failures.len() - errors;
+    println!("{} rules, {errors} errors, {warnings} warnings", set.len());
+    Ok(match (errors, warnings) {
+        (0, 0) => ExitCode::SUCCESS,
+        (0, _) if !deny_warnings => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    })
+}
+
+#[cfg(feature = "rule-files")]
+fn load_rules(path: &str) -> anyhow::Result<RuleSet> {
+    let json = std::fs::read_to_string(path).with_context(|| format!("reading {path}"))?;
+    RuleSet::leak_from_json(&json).with_context(|| format!("loading {path}"))
+}
+
+#[cfg(not(feature = "rule-files"))]
+fn load_rules(_path: &str) -> anyhow::Result<RuleSet> {
+    anyhow::bail!("`--rules` needs the `rule-files` feature")
+}
+
+/// The `"http://..."` and `"https://..."` string literals of a source file.
+fn string_urls(source: &str) -> impl Iterator<Item = &str> {
+    source
+        .split('"')
+        .skip(1)
+        .step_by(2)
+        .filter(|s| s.starts_with("http://") || s.starts_with("https://"))
+}
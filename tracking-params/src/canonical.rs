@@ -0,0 +1,228 @@
+use url::Url;
+
+use crate::{query_key, rules::DESKTOP_HOSTS};
+
+/// Steps that rewrite a cleaned url to a canonical form, so that urls pointing to the
+/// same page compare equal (eg. to deduplicate links). All of them are disabled by default.
+///
+/// Note: [`url::Url`] already lowercases the scheme and host of `http(s)` urls and removes
+/// their default port when parsing them, and cleaning already drops empty query and fragment
+/// markers, these steps only report a change for urls that still have them.
+///
+/// ```rust
+/// use tracking_params::{CanonicalStep, Canonicalize, Options};
+///
+/// let options = Options {
+///     canonicalize: Canonicalize::all(),
+///     ..Default::default()
+/// };
+/// let url = url::Url::parse("https://m.youtube.com/watch?v=dQw4w9WgXcQ&t=5&feature=share")?;
+/// let cleaned = tracking_params::clean_with(url, &options);
+///
+/// assert_eq!(cleaned.to_string(), "https://www.youtube.com/watch?t=5&v=dQw4w9WgXcQ");
+/// assert_eq!(
+///     cleaned.canonical_steps(),
+///     [CanonicalStep::SortParams, CanonicalStep::DesktopHost]
+/// );
+/// # Ok::<_, url::ParseError>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Canonicalize {
+    /// `FOO://Example.com` to `foo://example.com`.
+    pub lowercase: bool,
+    /// `ftp://example.com:21/` to `ftp://example.com/`.
+    pub remove_default_port: bool,
+    /// `https://example.com/?#` to `https://example.com/`.
+    pub remove_empty_markers: bool,
+    /// `?b=1&a=2` to `?a=2&b=1` for sites whose rules are [`crate::Rule::order_insensitive`].
+    /// The values of a repeated param keep their order.
+    pub sort_params: bool,
+    /// `%3a` to `%3A`.
+    pub uppercase_percent_encoding: bool,
+    /// `m.youtube.com` to `www.youtube.com`, for the sites whose mobile host is known.
+    pub desktop_host: bool,
+}
+
+impl Canonicalize {
+    /// All the steps enabled.
+    pub fn all() -> Self {
+        Self {
+            lowercase: true,
+            remove_default_port: true,
+            remove_empty_markers: true,
+            sort_params: true,
+            uppercase_percent_encoding: true,
+            desktop_host: true,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+/// A step of [`Canonicalize`] that changed a url.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanonicalStep {
+    Lowercase,
+    RemoveDefaultPort,
+    RemoveEmptyMarkers,
+    SortParams,
+    UppercasePercentEncoding,
+    DesktopHost,
+}
+
+/// Applies the enabled `steps` to `url` and returns the ones that changed it.
+pub(crate) fn canonicalize(
+    mut url: Url,
+    steps: &Canonicalize,
+    order_insensitive: bool,
+) -> (Url, Vec<CanonicalStep>) {
+    let mut applied = vec![];
+
+    if steps.lowercase {
+        // The scheme is always lowercased by the parser.
+        let host = url.host_str().map(str::to_ascii_lowercase);
+        if host.as_deref() != url.host_str() && url.set_host(host.as_deref()).is_ok() {
+            applied.push(CanonicalStep::Lowercase);
+        }
+    }
+
+    if steps.remove_default_port && url.port().is_some() && url.port() == default_port(&url) {
+        let _ = url.set_port(None);
+        applied.push(CanonicalStep::RemoveDefaultPort);
+    }
+
+    if steps.remove_empty_markers && (url.query() == Some("") || url.fragment() == Some("")) {
+        if url.query() == Some("") {
+            url.set_query(None);
+        }
+        if url.fragment() == Some("") {
+            url.set_fragment(None);
+        }
+        applied.push(CanonicalStep::RemoveEmptyMarkers);
+    }
+
+    if steps.sort_params && order_insensitive {
+        if let Some(query) = url.query() {
+            let mut pairs = query.split('&').collect::<Vec<_>>();
+            // Stable, so the values of a repeated param keep their order.
+            pairs.sort_by_key(|pair| query_key(pair));
+            let sorted = pairs.join("&");
+            if sorted != query {
+                url.set_query(Some(&sorted));
+                applied.push(CanonicalStep::SortParams);
+            }
+        }
+    }
+
+    if steps.uppercase_percent_encoding {
+        let path = uppercase_escapes(url.path());
+        let query = url.query().and_then(uppercase_escapes);
+        let fragment = url.fragment().and_then(uppercase_escapes);
+
+        if path.is_some() || query.is_some() || fragment.is_some() {
+            if let Some(path) = path {
+                url.set_path(&path);
+            }
+            if let Some(query) = query {
+                url.set_query(Some(&query));
+            }
+            if let Some(fragment) = fragment {
+                url.set_fragment(Some(&fragment));
+            }
+            applied.push(CanonicalStep::UppercasePercentEncoding);
+        }
+    }
+
+    if steps.desktop_host {
+        if let Some(host) = url.host_str().and_then(desktop_host) {
+            if url.set_host(Some(&host)).is_ok() {
+                applied.push(CanonicalStep::DesktopHost);
+            }
+        }
+    }
+
+    (url, applied)
+}
+
+fn default_port(url: &Url) -> Option<u16> {
+    match url.scheme() {
+        "http" | "ws" => Some(80),
+        "https" | "wss" => Some(443),
+        "ftp" => Some(21),
+        _ => None,
+    }
+}
+
+/// `input` with the hex digits of its percent escapes uppercased, `None` when they already are.
+fn uppercase_escapes(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let is_lowercase_escape = |i: usize| {
+        bytes[i] == b'%'
+            && bytes.len() > i + 2
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+            && (bytes[i + 1].is_ascii_lowercase() || bytes[i + 2].is_ascii_lowercase())
+    };
+    if !(0..bytes.len()).any(is_lowercase_escape) {
+        return None;
+    }
+
+    let mut output = bytes.to_vec();
+    for i in 0..bytes.len() {
+        if is_lowercase_escape(i) {
+            output[i + 1].make_ascii_uppercase();
+            output[i + 2].make_ascii_uppercase();
+        }
+    }
+
+    // Only ascii hex digits were changed.
+    String::from_utf8(output).ok()
+}
+
+/// The desktop host of a known mobile `host`.
+fn desktop_host(host: &str) -> Option<String> {
+    DESKTOP_HOSTS.iter().find_map(|(mobile, desktop)| {
+        if host == *mobile {
+            Some(desktop.to_string())
+        } else {
+            host.strip_suffix(mobile)
+                .and_then(|sub| sub.strip_suffix('.'))
+                .map(|sub| format!("{sub}.{desktop}"))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uppercases_escapes() {
+        assert_eq!(
+            uppercase_escapes("/a%3ab%2F"),
+            Some("/a%3Ab%2F".to_string())
+        );
+        assert_eq!(uppercase_escapes("/a%3Ab%2F"), None);
+        assert_eq!(uppercase_escapes("/a%zz%a"), None);
+    }
+
+    #[test]
+    fn maps_mobile_hosts() {
+        assert_eq!(
+            desktop_host("m.youtube.com").as_deref(),
+            Some("www.youtube.com")
+        );
+        assert_eq!(
+            desktop_host("en.m.wikipedia.org").as_deref(),
+            Some("en.wikipedia.org")
+        );
+        assert_eq!(
+            desktop_host("mobile.twitter.com").as_deref(),
+            Some("twitter.com")
+        );
+        assert_eq!(desktop_host("am.youtube.com"), None);
+        assert_eq!(desktop_host("www.youtube.com"), None);
+    }
+}
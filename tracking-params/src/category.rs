@@ -0,0 +1,179 @@
+/// What the params of a rule are used for, see [`crate::Rule::with_category`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// Campaign and visit attribution (eg. `utm_source`, `pk_campaign`).
+    Analytics,
+    /// Identifiers of an ad click (eg. `gclid`, `fbclid`, `msclkid`).
+    ClickId,
+    /// Commission of the site or person that shared the link (eg. Amazon's `tag`).
+    Affiliate,
+    /// Added by the share buttons of social networks and apps (eg. `igshid`, `si`).
+    SocialShare,
+    /// Identifiers of a newsletter and its recipient (eg. `mc_eid`, `_hsenc`).
+    EmailMarketing,
+    /// State of the search or session the link was copied from (eg. `ved`, `qid`).
+    SessionState,
+    /// Params that can change what the page shows (eg. Amazon's `psc`, `amp`).
+    SiteFunctional,
+}
+
+impl Category {
+    pub const ALL: [Category; 7] = [
+        Category::Analytics,
+        Category::ClickId,
+        Category::Affiliate,
+        Category::SocialShare,
+        Category::EmailMarketing,
+        Category::SessionState,
+        Category::SiteFunctional,
+    ];
+
+    /// `click_id`, `social_share`...
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Analytics => "analytics",
+            Category::ClickId => "click_id",
+            Category::Affiliate => "affiliate",
+            Category::SocialShare => "social_share",
+            Category::EmailMarketing => "email_marketing",
+            Category::SessionState => "session_state",
+            Category::SiteFunctional => "site_functional",
+        }
+    }
+
+    /// The category with the given [`Category::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    fn bit(&self) -> u8 {
+        1 << *self as u8
+    }
+}
+
+/// Ready made sets of [`Categories`] to remove.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Only the params that never change the page or who gets credit for the visit:
+    /// analytics, click ids, email marketing and social share params.
+    Safe,
+    /// [`Preset::Safe`] plus affiliate and session state params.
+    Standard,
+    /// All the categories, the default.
+    Strict,
+}
+
+impl Preset {
+    /// `safe`, `standard` or `strict`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "safe" => Some(Preset::Safe),
+            "standard" => Some(Preset::Standard),
+            "strict" => Some(Preset::Strict),
+            _ => None,
+        }
+    }
+}
+
+/// The categories of rules applied when cleaning, see [`crate::Options::categories`].
+///
+/// ```rust
+/// use tracking_params::{Categories, Category, Options, Preset};
+///
+/// // Keep affiliate tags but remove everything else.
+/// let options = Options {
+///     categories: Categories::from(Preset::Strict).without(Category::Affiliate),
+///     ..Default::default()
+/// };
+/// let url = url::Url::parse("https://www.amazon.com/dp/B08N5WRWNW?tag=blog-20&qid=1")?;
+///
+/// assert_eq!(
+///     tracking_params::clean_with(url, &options).to_string(),
+///     "https://www.amazon.com/dp/B08N5WRWNW?tag=blog-20"
+/// );
+/// # Ok::<_, url::ParseError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Categories(u8);
+
+impl Categories {
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    pub fn all() -> Self {
+        Self((1 << Category::ALL.len()) - 1)
+    }
+
+    pub fn contains(&self, category: Category) -> bool {
+        self.0 & category.bit() != 0
+    }
+
+    pub fn with(self, category: Category) -> Self {
+        Self(self.0 | category.bit())
+    }
+
+    pub fn without(self, category: Category) -> Self {
+        Self(self.0 & !category.bit())
+    }
+
+    /// The categories in the set, in the order of [`Category::ALL`].
+    pub fn iter(&self) -> impl Iterator<Item = Category> + '_ {
+        Category::ALL.into_iter().filter(|c| self.contains(*c))
+    }
+}
+
+/// All the categories, like before rules had one.
+impl Default for Categories {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl From<Preset> for Categories {
+    fn from(preset: Preset) -> Self {
+        let safe = Self::empty()
+            .with(Category::Analytics)
+            .with(Category::ClickId)
+            .with(Category::EmailMarketing)
+            .with(Category::SocialShare);
+
+        match preset {
+            Preset::Safe => safe,
+            Preset::Standard => safe.with(Category::Affiliate).with(Category::SessionState),
+            Preset::Strict => Self::all(),
+        }
+    }
+}
+
+impl FromIterator<Category> for Categories {
+    fn from_iter<I: IntoIterator<Item = Category>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::empty(), Self::with)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_nested() {
+        let safe = Categories::from(Preset::Safe);
+        let standard = Categories::from(Preset::Standard);
+
+        assert!(safe.iter().all(|c| standard.contains(c)));
+        assert!(!safe.contains(Category::Affiliate));
+        assert!(!standard.contains(Category::SiteFunctional));
+        assert_eq!(Categories::from(Preset::Strict), Categories::default());
+        assert_eq!(Categories::all().iter().count(), Category::ALL.len());
+    }
+
+    #[test]
+    fn parses_names() {
+        for category in Category::ALL {
+            assert_eq!(Category::from_name(category.name()), Some(category));
+        }
+        assert_eq!(Category::from_name("tracking"), None);
+        assert_eq!(Preset::from_name("standard"), Some(Preset::Standard));
+    }
+}
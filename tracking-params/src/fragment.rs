@@ -0,0 +1,76 @@
+use std::borrow::Cow;
+
+use crate::{clean_pairs, Matched};
+
+/// The shapes of url fragments, each of them cleaned differently.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Fragment<'a> {
+    /// `#/products?id=1&utm_source=x`, the route of a hash routed single page app
+    /// (Angular, Vue ...) with an optional query that is cleaned like the query of a url.
+    Route(&'a str),
+    /// `#from=home&ref_src=x`, params that are cleaned like a query.
+    Params(&'a str),
+    /// `#:~:text=foo` or `#section:~:text=foo`, a text fragment that is kept as is.
+    TextDirective(&'a str),
+    /// `#section`, an anchor that is kept as is.
+    Anchor(&'a str),
+}
+
+impl<'a> Fragment<'a> {
+    pub(crate) fn parse(fragment: &'a str) -> Self {
+        if fragment.contains(":~:") {
+            return Self::TextDirective(fragment);
+        }
+
+        // `#!/` is the hashbang prefix of AngularJS routes.
+        if fragment.starts_with('/') || fragment.starts_with("!/") {
+            return Self::Route(fragment);
+        }
+
+        if fragment.contains('=') {
+            Self::Params(fragment)
+        } else {
+            Self::Anchor(fragment)
+        }
+    }
+
+    /// The fragment without the params removed by `rules`, borrowed when nothing was removed.
+    pub(crate) fn clean(&self, rules: &Matched) -> Cow<'a, str> {
+        match *self {
+            Self::Route(route) => match route.split_once('?') {
+                Some((path, query)) => match clean_pairs(query, rules) {
+                    Cow::Borrowed(_) if !query.is_empty() => Cow::Borrowed(route),
+                    cleaned if cleaned.is_empty() => Cow::Owned(path.to_owned()),
+                    cleaned => Cow::Owned(format!("{path}?{cleaned}")),
+                },
+                None => Cow::Borrowed(route),
+            },
+            Self::Params(params) => clean_pairs(params, rules),
+            Self::TextDirective(fragment) | Self::Anchor(fragment) => Cow::Borrowed(fragment),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_shape() {
+        for (fragment, expected) in [
+            ("/products?id=1", Fragment::Route("/products?id=1")),
+            ("!/products", Fragment::Route("!/products")),
+            (
+                "from=home&ref_src=x",
+                Fragment::Params("from=home&ref_src=x"),
+            ),
+            ("section=2", Fragment::Params("section=2")),
+            (":~:text=foo", Fragment::TextDirective(":~:text=foo")),
+            ("top:~:text=a=b", Fragment::TextDirective("top:~:text=a=b")),
+            ("section", Fragment::Anchor("section")),
+            ("", Fragment::Anchor("")),
+        ] {
+            assert_eq!(Fragment::parse(fragment), expected, "{fragment}");
+        }
+    }
+}
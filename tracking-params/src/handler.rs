@@ -0,0 +1,341 @@
+use std::{borrow::Cow, fmt};
+
+use url::{Position, Url};
+
+use crate::{pii::decode_base64, M};
+
+/// A declarative handler, see [`crate::Rule::with_handler_spec`].
+///
+/// Unlike a closure it can be compared, printed, and loaded from and written to rule files
+/// (see [`crate::RuleSet::leak_from_json`] and [`crate::RuleSet::to_json`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "rule-files",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum HandlerSpec {
+    /// Replaces the url with the first value of `keys` that is a url once decoded,
+    /// when the path contains all of `path_contains`
+    /// (eg. `https://www.google.com/url?q=https://example.com/`).
+    ExtractFromQuery {
+        keys: Vec<&'static str>,
+        path_contains: Vec<&'static str>,
+        decode: Vec<Decoding>,
+    },
+    /// Replaces the url with the rest of its path and query after `after`, when it is a url
+    /// once decoded (eg. `https://out.example.com/redirect/https://example.com/`).
+    ExtractFromPathSuffix {
+        after: &'static str,
+        decode: Vec<Decoding>,
+    },
+    /// Replaces the host when it is exactly `from`.
+    RewriteHost {
+        from: &'static str,
+        to: &'static str,
+    },
+    /// Removes the path segments matching the matcher (eg. `StartsWith("ref=")`).
+    StripPath(M),
+    /// Applies the specs in order.
+    Chain(Vec<HandlerSpec>),
+}
+
+/// How an extracted value is decoded before being parsed as a url, decodings are applied in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "rule-files",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Decoding {
+    /// `https%3A%2F%2Fexample.com`, query values are already decoded once.
+    Percent,
+    /// Standard or url-safe, with or without padding.
+    Base64,
+    /// The string claim of the payload of a JSON Web Token, its signature is not checked.
+    Jwt(&'static str),
+}
+
+impl HandlerSpec {
+    /// The url after this handler, `url` when it doesn't apply.
+    pub fn apply(&self, url: Url) -> Url {
+        match self {
+            HandlerSpec::ExtractFromQuery {
+                keys,
+                path_contains,
+                decode,
+            } => {
+                if !path_contains.iter().all(|p| url.path().contains(p)) {
+                    return url;
+                }
+                let found = keys.iter().find_map(|key| {
+                    url.query_pairs()
+                        .filter(|(k, _)| k == key)
+                        .find_map(|(_, value)| decoded_url(&value, decode))
+                });
+                found.unwrap_or(url)
+            }
+            HandlerSpec::ExtractFromPathSuffix { after, decode } => {
+                let path_and_query = &url[Position::BeforePath..Position::AfterQuery];
+                let found = path_and_query
+                    .find(after)
+                    .and_then(|idx| decoded_url(&path_and_query[idx + after.len()..], decode));
+                found.unwrap_or(url)
+            }
+            HandlerSpec::RewriteHost { from, to } => {
+                let mut url = url;
+                if url.host_str() == Some(from) {
+                    // Only fails for urls without a host, which `from` can't be.
+                    let _ = url.set_host(Some(to));
+                }
+                url
+            }
+            HandlerSpec::StripPath(matcher) => {
+                let Some(segments) = url.path_segments() else {
+                    return url;
+                };
+                let segments = segments.collect::<Vec<_>>();
+                let kept = segments
+                    .iter()
+                    .filter(|s| !matcher.matches_str(Some(s)))
+                    .copied()
+                    .collect::<Vec<_>>();
+                if kept.len() == segments.len() {
+                    return url;
+                }
+
+                let path = format!("/{}", kept.join("/"));
+                let mut url = url;
+                url.set_path(&path);
+                url
+            }
+            HandlerSpec::Chain(specs) => specs.iter().fold(url, |url, spec| spec.apply(url)),
+        }
+    }
+}
+
+impl Decoding {
+    fn decode<'a>(&self, value: &'a str) -> Option<Cow<'a, str>> {
+        match self {
+            Decoding::Percent => urlencoding::decode(value).ok(),
+            Decoding::Base64 => decode_base64(value).map(Cow::Owned),
+            Decoding::Jwt(claim) => {
+                let payload = decode_base64(value.split('.').nth(1)?)?;
+                json_string(&payload, claim).map(Cow::Owned)
+            }
+        }
+    }
+}
+
+fn decoded_url(value: &str, decode: &[Decoding]) -> Option<Url> {
+    let mut value = Cow::Borrowed(value);
+    for decoding in decode {
+        value = Cow::Owned(decoding.decode(&value)?.into_owned());
+    }
+    Url::parse(&value).ok()
+}
+
+/// The string value of `key` in the JSON object `json`, enough for the flat payloads of tokens.
+fn json_string(json: &str, key: &str) -> Option<String> {
+    let quoted = format!("\"{key}\"");
+    json.match_indices(&quoted).find_map(|(idx, _)| {
+        let rest = json[idx + quoted.len()..].trim_start().strip_prefix(':')?;
+        let mut chars = rest.trim_start().strip_prefix('"')?.chars();
+        let mut value = String::new();
+        loop {
+            match chars.next()? {
+                '"' => return Some(value),
+                '\\' => match chars.next()? {
+                    'b' => value.push('\u{8}'),
+                    'f' => value.push('\u{c}'),
+                    'n' => value.push('\n'),
+                    'r' => value.push('\r'),
+                    't' => value.push('\t'),
+                    'u' => {
+                        let unit = hex_unit(&mut chars)?;
+                        let code = match unit {
+                            // A surrogate pair, the low surrogate is escaped right after.
+                            0xD800..=0xDBFF => {
+                                let low = chars
+                                    .next()
+                                    .zip(chars.next())
+                                    .filter(|escape| *escape == ('\\', 'u'))
+                                    .and_then(|_| hex_unit(&mut chars))
+                                    .filter(|low| (0xDC00..=0xDFFF).contains(low))?;
+                                0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)
+                            }
+                            unit => unit,
+                        };
+                        value.push(char::from_u32(code)?);
+                    }
+                    escaped @ ('"' | '\\' | '/') => value.push(escaped),
+                    _ => return None,
+                },
+                c => value.push(c),
+            }
+        }
+    })
+}
+
+/// The 4 hex digits of a `\uXXXX` escape.
+fn hex_unit(chars: &mut std::str::Chars) -> Option<u32> {
+    let hex = chars.by_ref().take(4).collect::<String>();
+    if hex.len() != 4 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(&hex, 16).ok()
+}
+
+/// What a rule does to the urls it matches before their params are removed.
+pub(crate) enum Handler {
+    Spec(HandlerSpec),
+    /// For what specs can't express, see [`crate::Rule::with_handler`].
+    Custom(Box<dyn Fn(Url) -> Url + Sync + Send>),
+}
+
+impl Handler {
+    pub(crate) fn apply(&self, url: Url) -> Url {
+        match self {
+            Handler::Spec(spec) => spec.apply(url),
+            Handler::Custom(handler) => handler(url),
+        }
+    }
+}
+
+impl fmt::Debug for Handler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Handler::Spec(spec) => spec.fmt(f),
+            Handler::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(spec: HandlerSpec, url: &str) -> String {
+        spec.apply(Url::parse(url).unwrap()).to_string()
+    }
+
+    #[test]
+    fn extracts_from_query() {
+        let spec = || HandlerSpec::ExtractFromQuery {
+            keys: vec!["u", "url"],
+            path_contains: vec!["/out"],
+            decode: vec![],
+        };
+
+        assert_eq!(
+            apply(
+                spec(),
+                "https://example.com/out?u=invalid&url=https%3A%2F%2Fexample.org%2F"
+            ),
+            "https://example.org/"
+        );
+        assert_eq!(
+            apply(spec(), "https://example.com/in?url=https://example.org/"),
+            "https://example.com/in?url=https://example.org/"
+        );
+    }
+
+    #[test]
+    fn extracts_from_path_suffix() {
+        let spec = || HandlerSpec::ExtractFromPathSuffix {
+            after: "/out/",
+            decode: vec![Decoding::Percent],
+        };
+
+        assert_eq!(
+            apply(
+                spec(),
+                "https://example.com/out/https://example.org/page?id=1"
+            ),
+            "https://example.org/page?id=1"
+        );
+        assert_eq!(
+            apply(
+                spec(),
+                "https://example.com/out/https%3A%2F%2Fexample.org%2F"
+            ),
+            "https://example.org/"
+        );
+        assert_eq!(
+            apply(spec(), "https://example.com/out/page"),
+            "https://example.com/out/page"
+        );
+    }
+
+    #[test]
+    fn decodes_values() {
+        let spec = |decode| HandlerSpec::ExtractFromQuery {
+            keys: vec!["t"],
+            path_contains: vec![],
+            decode,
+        };
+        // {"alg":"none"}.{"sub":"1","url":"https:\/\/example.org\/"}.
+        let jwt = "eyJhbGciOiJub25lIn0.eyJzdWIiOiIxIiwidXJsIjoiaHR0cHM6XC9cL2V4YW1wbGUub3JnXC8ifQ.";
+
+        assert_eq!(
+            apply(
+                spec(vec![Decoding::Base64]),
+                "https://example.com/?t=aHR0cHM6Ly9leGFtcGxlLm9yZy8"
+            ),
+            "https://example.org/"
+        );
+        assert_eq!(
+            apply(
+                spec(vec![Decoding::Jwt("url")]),
+                &format!("https://example.com/?t={jwt}")
+            ),
+            "https://example.org/"
+        );
+        assert_eq!(
+            apply(
+                spec(vec![Decoding::Jwt("sub")]),
+                &format!("https://example.com/?t={jwt}")
+            ),
+            format!("https://example.com/?t={jwt}")
+        );
+    }
+
+    #[test]
+    fn rewrites_urls() {
+        let spec = HandlerSpec::Chain(vec![
+            HandlerSpec::RewriteHost {
+                from: "old.example.com",
+                to: "www.example.com",
+            },
+            HandlerSpec::StripPath(M::StartsWith("ref=")),
+        ]);
+
+        assert_eq!(
+            apply(spec.clone(), "https://old.example.com/dp/1/ref=sr_1?q=1"),
+            "https://www.example.com/dp/1?q=1"
+        );
+        assert_eq!(
+            apply(spec, "https://shop.example.com/dp/1/"),
+            "https://shop.example.com/dp/1/"
+        );
+    }
+
+    #[test]
+    fn reads_json_strings() {
+        assert_eq!(
+            json_string(r#"{"a": "url", "url" : "x\"A"}"#, "url"),
+            Some("x\"A".to_string())
+        );
+        assert_eq!(json_string(r#"{"url": 1}"#, "url"), None);
+        assert_eq!(
+            json_string(r#"{"url": "a\r\b\f\/\\z"}"#, "url"),
+            Some("a\r\u{8}\u{c}/\\z".to_string())
+        );
+        assert_eq!(
+            json_string(r#"{"url": "\ud83d\ude00 \u00e9"}"#, "url"),
+            Some("\u{1F600} \u{e9}".to_string())
+        );
+        assert_eq!(json_string(r#"{"url": "\ud83d x"}"#, "url"), None);
+        assert_eq!(json_string(r#"{"url": "\x"}"#, "url"), None);
+    }
+}
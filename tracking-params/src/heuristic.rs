@@ -0,0 +1,231 @@
+//! Scoring of the query params that no rule knows, see [`crate::Options::aggressive`].
+use url::Url;
+
+use crate::rule_set::Matched;
+
+/// Params scoring more than this are removed.
+pub(crate) const THRESHOLD: f32 = 0.6;
+
+/// Values shorter than this are too short to be unique per click or visitor.
+const MIN_LENGTH: usize = 12;
+
+/// Keys ending with these are click identifiers (`gclid`, `fbclid`, `ttclid`, `x_click_id` ...).
+const CLICK_ID_SUFFIXES: &[&str] = &["clid", "click_id", "clickid"];
+
+/// Keys that sites commonly need to work (ids, searches, pagination, signed urls, logins)
+/// and are never removed whatever their value looks like.
+const FUNCTIONAL_KEYS: &[&str] = &[
+    "auth",
+    "code",
+    "hash",
+    "id",
+    "k",
+    "key",
+    "nonce",
+    "p",
+    "page",
+    "q",
+    "s",
+    "session",
+    "sid",
+    "sig",
+    "signature",
+    "state",
+    "t",
+    "ticket",
+    "token",
+    "v",
+];
+
+/// A query param removed because it looked like an opaque identifier instead of being
+/// listed by a rule, see [`crate::Cleaned::heuristic_removals`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeuristicRemoval {
+    /// The decoded key, eg. `xyzclid`.
+    pub key: String,
+    /// The `key=value` pair as it was in the query.
+    pub pair: String,
+    /// The position of the pair in the query, inserting `pair` back there undoes the removal.
+    pub index: usize,
+    /// How much the param looked like an identifier, between the threshold and `1.0`.
+    pub confidence: f32,
+}
+
+/// Removes the query params of `url` that look like opaque identifiers,
+/// unless the `rules` preserve them.
+pub(crate) fn clean_query(mut url: Url, rules: &Matched) -> (Url, Vec<HeuristicRemoval>) {
+    let Some(query) = url.query() else {
+        return (url, vec![]);
+    };
+
+    let mut removals = vec![];
+    let kept = query
+        .split('&')
+        .enumerate()
+        .filter(|(index, pair)| {
+            let Some((key, value)) = url::form_urlencoded::parse(pair.as_bytes()).next() else {
+                return true;
+            };
+            if rules.preserves(&key) || FUNCTIONAL_KEYS.contains(&&*key.to_ascii_lowercase()) {
+                return true;
+            }
+
+            let confidence = score(&key, &value);
+            if confidence <= THRESHOLD {
+                return true;
+            }
+            removals.push(HeuristicRemoval {
+                key: key.into_owned(),
+                pair: pair.to_string(),
+                index: *index,
+                confidence,
+            });
+            false
+        })
+        .map(|(_, pair)| pair)
+        .collect::<Vec<_>>()
+        .join("&");
+
+    if !removals.is_empty() {
+        url.set_query((!kept.is_empty()).then_some(kept.as_str()));
+    }
+    (url, removals)
+}
+
+/// How much the param looks like a tracking identifier, from `0.0` to `1.0`.
+///
+/// The shape of the value alone can't go over [`THRESHOLD`] unless it is long and
+/// very random, a key that looks like a click id is needed otherwise.
+pub(crate) fn score(key: &str, value: &str) -> f32 {
+    if value.len() < MIN_LENGTH || value.contains("://") {
+        return 0.0;
+    }
+
+    let key = key.to_ascii_lowercase();
+    let key_score = if CLICK_ID_SUFFIXES.iter().any(|s| key.ends_with(s)) {
+        0.5
+    } else if key.ends_with("_id") || key.ends_with("-id") {
+        0.2
+    } else {
+        0.0
+    };
+
+    let shape_score = match Shape::of(value) {
+        Shape::Uuid => 0.4,
+        Shape::Hex => 0.35,
+        Shape::Base64 => 0.3,
+        Shape::Text => 0.0,
+    };
+
+    let entropy = entropy(value);
+    let entropy_score = if entropy >= 4.5 {
+        0.25
+    } else if entropy >= 3.5 {
+        0.1
+    } else {
+        0.0
+    };
+
+    let length_score = if value.len() >= 32 { 0.1 } else { 0.0 };
+
+    f32::min(key_score + shape_score + entropy_score + length_score, 1.0)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Shape {
+    /// `f47ac10b-58cc-4372-a567-0e02b2c3d479`
+    Uuid,
+    /// `9f86d081884c7d65`, with both digits and letters.
+    Hex,
+    /// `Cj0KCQiA5rGuBhCnARIsAN11vgT`, with lowercase, uppercase and digits.
+    Base64,
+    Text,
+}
+
+impl Shape {
+    fn of(value: &str) -> Self {
+        let bytes = value.as_bytes();
+        let has = |f: fn(&u8) -> bool| bytes.iter().any(f);
+
+        let uuid = bytes.len() == 36
+            && bytes.iter().enumerate().all(|(i, b)| match i {
+                8 | 13 | 18 | 23 => *b == b'-',
+                _ => b.is_ascii_hexdigit(),
+            });
+        if uuid {
+            return Self::Uuid;
+        }
+
+        if bytes.len() >= 16
+            && bytes.iter().all(u8::is_ascii_hexdigit)
+            && has(u8::is_ascii_digit)
+            && has(u8::is_ascii_alphabetic)
+        {
+            return Self::Hex;
+        }
+
+        let base64 = value.trim_end_matches('=');
+        if base64.len() >= 20
+            && base64
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-_+/".contains(&b))
+            && has(u8::is_ascii_lowercase)
+            && has(u8::is_ascii_uppercase)
+            && has(u8::is_ascii_digit)
+        {
+            return Self::Base64;
+        }
+
+        Self::Text
+    }
+}
+
+/// Shannon entropy of the characters of `value`, in bits per character.
+fn entropy(value: &str) -> f32 {
+    let mut counts = [0usize; 256];
+    for b in value.bytes() {
+        counts[b as usize] += 1;
+    }
+
+    let len = value.len() as f32;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f32 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_shapes() {
+        assert_eq!(
+            Shape::of("f47ac10b-58cc-4372-a567-0e02b2c3d479"),
+            Shape::Uuid
+        );
+        assert_eq!(Shape::of("9f86d081884c7d659a2f"), Shape::Hex);
+        assert_eq!(Shape::of("1234567890123456"), Shape::Text);
+        assert_eq!(Shape::of("Cj0KCQiA5rGuBhCnARIsAN11vgT=="), Shape::Base64);
+        assert_eq!(Shape::of("summer-sale-2024-shoes"), Shape::Text);
+    }
+
+    #[test]
+    fn scores_identifiers() {
+        let random = "Cj0KCQiA5rGuBhCnARIsAN11vgTzXq8ePwLm3";
+
+        assert!(score("xyzclid", "Cj0KCQiA5rGuBhCnARIs") > THRESHOLD);
+        assert!(score("visitor_id", "f47ac10b-58cc-4372-a567-0e02b2c3d479") > THRESHOLD);
+        assert!(score("zx", random) > THRESHOLD);
+
+        assert!(score("xyzclid", "short") <= THRESHOLD);
+        assert!(score("order_id", "1234567890123456") <= THRESHOLD);
+        assert!(score("campaign", "summer-sale-2024-shoes") <= THRESHOLD);
+        assert!(score("sha", "9f86d081884c7d659a2f0aa0c55ad015a3bf4f1b") <= THRESHOLD);
+        assert!(score("next", "https://example.com/Cj0KCQiA5rGuBhCnARIs") <= THRESHOLD);
+    }
+}
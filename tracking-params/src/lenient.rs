@@ -0,0 +1,144 @@
+use url::Url;
+
+/// A fix applied to the input of [`crate::clean_lenient`] before parsing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// Leading or trailing whitespace and new lines were removed.
+    TrimmedWhitespace,
+    /// The url was wrapped in `<...>` or quotes.
+    Unwrapped,
+    /// Punctuation from the surrounding text (eg. `.` or `,`) was removed from the end.
+    TrimmedPunctuation,
+    /// `https://` was added to an input without scheme whose host is a domain.
+    AddedScheme,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum CleanError {
+    #[error("Empty input")]
+    Empty,
+    #[error("Missing scheme, `{0}` is not a domain")]
+    MissingScheme(String),
+    #[error("Invalid url: {source}")]
+    InvalidUrl {
+        #[source]
+        source: url::ParseError,
+        /// Fixes applied to the input before it failed to parse.
+        normalizations: Vec<Normalization>,
+    },
+}
+
+const QUOTES: &[(char, char)] = &[
+    ('<', '>'),
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+    ('“', '”'),
+    ('‘', '’'),
+];
+
+/// Parses `input` after removing what often surrounds urls pasted from text.
+pub(crate) fn parse(input: &str) -> Result<(Url, Vec<Normalization>), CleanError> {
+    let mut normalizations = vec![];
+    let mut input = input;
+    let mut apply = |before: &str, after, normalization| {
+        if after != before && !normalizations.contains(&normalization) {
+            normalizations.push(normalization);
+        }
+        after
+    };
+
+    // Wrappers and punctuation can be nested, eg. `"<https://example.com>".`
+    loop {
+        let before = input;
+        input = apply(input, input.trim(), Normalization::TrimmedWhitespace);
+        input = apply(input, unwrap(input), Normalization::Unwrapped);
+        input = apply(
+            input,
+            trim_punctuation(input),
+            Normalization::TrimmedPunctuation,
+        );
+        if input == before {
+            break;
+        }
+    }
+
+    if input.is_empty() {
+        return Err(CleanError::Empty);
+    }
+
+    let with_scheme;
+    // Only a `://` before the path counts, urls in the query (eg. `?url=https://...`) don't.
+    let host = input.split(['/', '?', '#']).next().unwrap_or_default();
+    if !input[host.len()..].starts_with("//") && is_domain(host) {
+        with_scheme = format!("https://{input}");
+        input = apply(input, &with_scheme, Normalization::AddedScheme);
+    }
+
+    match Url::parse(input) {
+        Ok(url) => Ok((url, normalizations)),
+        Err(url::ParseError::RelativeUrlWithoutBase) => Err(CleanError::MissingScheme(
+            input
+                .split(['/', '?', '#'])
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        )),
+        Err(source) => Err(CleanError::InvalidUrl {
+            source,
+            normalizations,
+        }),
+    }
+}
+
+fn unwrap(input: &str) -> &str {
+    QUOTES
+        .iter()
+        .find_map(|(open, close)| input.strip_prefix(*open)?.strip_suffix(*close))
+        .unwrap_or(input)
+}
+
+/// `input` without the punctuation that ends a sentence or a parenthesis around the url.
+///
+/// A closing parenthesis is kept when it has an opening one in the url
+/// (eg. `https://en.wikipedia.org/wiki/Rust_(programming_language)`).
+pub(crate) fn trim_punctuation(input: &str) -> &str {
+    let mut input = input;
+    loop {
+        let trimmed = input.trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(rest) if rest.matches('(').count() < trimmed.matches(')').count() => rest,
+            _ => trimmed,
+        };
+        if trimmed == input {
+            return input;
+        }
+        input = trimmed;
+    }
+}
+
+/// `example.com`, `example.com:8080` or `192.168.0.1`. A host without a dot, or whose last
+/// label is not a top level domain (eg. `3.14`, `1.2.3` or `e.g`), is never guessed to be one.
+fn is_domain(host: &str) -> bool {
+    let (domain, port) = match host.rsplit_once(':') {
+        Some((domain, port)) => (domain, Some(port)),
+        None => (host, None),
+    };
+    let labels = domain.split('.').collect::<Vec<_>>();
+
+    let labels_ok = labels.len() > 1
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        });
+    let is_ipv4 = labels.len() == 4 && labels.iter().all(|label| label.parse::<u8>().is_ok());
+    let tld = labels.last().copied().unwrap_or_default();
+    let is_tld = tld.starts_with("xn--")
+        || (tld.chars().count() >= 2 && tld.chars().all(char::is_alphabetic));
+
+    labels_ok
+        && (is_ipv4 || is_tld)
+        && port.is_none_or(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()))
+}
@@ -13,27 +13,102 @@
 //!
 //! # Ok::<_, url::ParseError>(())
 //! ```
+//!
+//! ## Features
+//! * `resolver`: follows the redirects of url shorteners before cleaning, see [`resolver`].
+//! * `rayon`: cleans the urls given to [`clean_batch`] in parallel.
+//! * `rule-files`: loads rules from JSON files, see [`RuleSet::leak_from_json`].
+//!
+//! ## Guarantees
+//! For the same rules and [`Options`]:
+//! * Cleaning is idempotent, `clean(clean(x)) == clean(x)`. Handlers are run again on the
+//!   url they return until it no longer changes, so the rules of the destination of nested
+//!   redirectors are applied too. Handlers rewriting a url back and forth stop at the first
+//!   url seen twice.
+//! * Cleaning only removes parameters: the scheme, host and path are unchanged and the query
+//!   and fragment params that are kept stay in order, byte for byte. The exceptions are
+//!   handlers replacing a url with its destination and the options that rewrite urls
+//!   ([`Options::expand_short_urls`], [`Options::nested_urls_depth`], [`Options::pii`]
+//!   redaction and [`Options::canonicalize`]).
+//!
+//! Both are checked by property tests on urls built from the builtin rules, and by the
+//! `clean_str` fuzz target (`cargo +nightly fuzz run clean_str` in `tracking-params/`).
+use std::borrow::Cow;
+
 use derivative::Derivative;
-use url::Url;
+use url::{Position, Url};
 
+mod canonical;
+mod category;
+mod fragment;
+mod handler;
+mod heuristic;
+mod lenient;
+mod lint;
+mod meta;
+mod nested;
+mod pii;
+#[cfg(test)]
+mod properties;
+mod raw;
+#[cfg(feature = "resolver")]
+pub mod resolver;
+#[cfg(feature = "rule-files")]
+mod rule_file;
+mod rule_set;
 mod rules;
+mod safety;
+mod schemes;
+mod verify;
+
+pub use canonical::{CanonicalStep, Canonicalize};
+pub use category::{Categories, Category, Preset};
+use fragment::Fragment;
+use handler::Handler;
+pub use handler::{Decoding, HandlerSpec};
+pub use heuristic::HeuristicRemoval;
+pub use lenient::{CleanError, Normalization};
+pub use lint::{Lint, LintKind};
+pub use meta::{RuleChange, RuleMeta, RuleSetDiff, RuleSetVersion};
+pub use pii::{PiiCategory, PiiFinding, PiiPolicy, REDACTED};
+#[cfg(feature = "rule-files")]
+pub use rule_file::RuleFileError;
+use rule_set::Matched;
+pub use rule_set::RuleSet;
+pub use safety::{Idn, Safety, SchemeRisk, Warning};
+pub use verify::ExampleFailure;
 
 #[derive(Derivative)]
 #[derivative(Debug)]
-pub(crate) struct Rule {
-    /// List of domains for which this rule applies.
+pub struct Rule {
+    /// Id, description and sources of this rule.
+    meta: RuleMeta,
+    /// List of domains for which this rule applies, matched against the `host[:port]/path`
+    /// of urls (eg. `www.example.com:8080/a/b`).
     host_path: Vec<M>,
-    /// List of query string and fragment params to remove.
-    params: Vec<M>,
-    /// Handler to run any specific code for this rule.
+    /// List of query string and fragment params to remove, with their category when it
+    /// is not the one of the rule (see [`Rule::params_in`]).
+    params: Vec<(M, Option<Category>)>,
+    /// List of query string params the matching sites need, they are never removed
+    /// by [`Options::aggressive`].
+    preserve: Vec<M>,
+    /// What the `params` are used for, the params whose category is not in
+    /// [`Options::categories`] are kept.
+    category: Category,
+    /// Whether the order of the query params doesn't matter for the matching sites,
+    /// so [`Canonicalize::sort_params`] can sort them.
+    order_insensitive: bool,
+    /// Urls and what they are cleaned to, checked by [`RuleSet::verify`].
+    #[derivative(Debug = "ignore")]
+    examples: &'static [(&'static str, &'static str)],
+    /// Handler to run any specific code for this rule, a [`HandlerSpec`] or a closure.
     ///
-    /// When defined, the handler run run before removing the matching
+    /// When defined, the handler runs before removing the matching
     /// params from the input url (defined in `params` field).
-    /// The handler can change or return a completely different Url.
-    /// Note: the handler is expected (although not validated for perf reason)
-    /// to return url that belongs to the same origin and treated as such because
-    /// * any matching params will still be removed later even if it's a different origin.
-    /// * any defined rule for that new origin won't be applied
+    /// The handler can change or return a completely different Url, the handlers of the
+    /// rules matching the returned url are then run on it, and so on until the url no longer
+    /// changes or comes back to one already seen (eg. two handlers rewriting a host back and
+    /// forth). The params of the rules matching that final url are then removed.
     ///
     /// A common use for handler function is to extract destination url from a query string
     /// from the input url. Consider the following link when click on a google search result:
@@ -42,22 +117,164 @@ pub(crate) struct Rule {
     ///
     /// We can extract the destination url from the `q` or `url` query string (whichever is present)
     /// and skip sending traffic to `/url` endpoint. For such cases you can use
-    /// the defined [`rules::extract_link_from_query_string`] function to extract valid url
-    /// from one or many query strings.
+    /// [`HandlerSpec::ExtractFromQuery`] to extract a valid url from one or many query strings.
+    handler: Option<Handler>,
+    /// Kinds of [`Lint`]s that [`RuleSet::lint`] doesn't report for this rule.
+    allowed_lints: Vec<LintKind>,
+}
+
+impl Rule {
+    /// Creates a rule that removes the `params` from urls matching any of `host_path`.
     ///
-    #[derivative(Debug = "ignore")]
-    handler: Option<Box<dyn Fn(Url) -> Url + Sync + Send>>,
+    /// **The category of the rule is [`Category::Analytics`]** until it is set with
+    /// [`Rule::with_category`], add the params of other categories with [`Rule::params_in`].
+    pub fn new(host_path: Vec<M>, params: Vec<M>) -> Self {
+        Self {
+            meta: RuleMeta::default(),
+            host_path,
+            params: params.into_iter().map(|param| (param, None)).collect(),
+            preserve: vec![],
+            category: Category::Analytics,
+            order_insensitive: false,
+            examples: &[],
+            handler: None,
+            allowed_lints: vec![],
+        }
+    }
+
+    /// Sets the id, description and sources of this rule.
+    pub fn with_meta(mut self, meta: RuleMeta) -> Self {
+        self.meta = meta;
+        self
+    }
+
+    pub fn meta(&self) -> &RuleMeta {
+        &self.meta
+    }
+
+    /// Sets what the `params` of this rule are used for.
+    pub fn with_category(mut self, category: Category) -> Self {
+        self.category = category;
+        self
+    }
+
+    /// Adds params of another category than the one of the rule, so a site is
+    /// written as a single rule.
+    ///
+    /// ```rust
+    /// use tracking_params::{Category, Rule, M};
+    ///
+    /// let rule = Rule::new(vec![M::Domain("example.com")], vec![M::Exact("qid")])
+    ///     .with_category(Category::SessionState)
+    ///     .params_in(Category::Affiliate, vec![M::Exact("tag")]);
+    /// ```
+    pub fn params_in(mut self, category: Category, params: Vec<M>) -> Self {
+        self.params
+            .extend(params.into_iter().map(|param| (param, Some(category))));
+        self
+    }
+
+    /// The params of this rule and their category.
+    pub(crate) fn params(&self) -> impl Iterator<Item = (&M, Category)> {
+        self.params
+            .iter()
+            .map(|(param, category)| (param, category.unwrap_or(self.category)))
+    }
+
+    /// Marks the matching sites as not caring about the order of query params,
+    /// see [`Canonicalize::sort_params`].
+    pub fn order_insensitive(mut self) -> Self {
+        self.order_insensitive = true;
+        self
+    }
+
+    /// Params the matching sites need, see [`Options::aggressive`].
+    pub fn preserving(mut self, params: Vec<M>) -> Self {
+        self.preserve = params;
+        self
+    }
+
+    /// Stops [`RuleSet::lint`] from reporting `kind` for this rule, for warnings that
+    /// are intended (eg. a `Contains` host for a service with many top level domains).
+    pub fn allowing_lint(mut self, kind: LintKind) -> Self {
+        self.allowed_lints.push(kind);
+        self
+    }
+
+    /// Urls this rule applies to and what they are cleaned to (by the whole [`RuleSet`]),
+    /// see [`RuleSet::verify`].
+    pub fn with_examples(mut self, examples: &'static [(&'static str, &'static str)]) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    /// Sets the handler of this rule, see [`Rule::handler`](#structfield.handler).
+    pub fn with_handler_spec(mut self, spec: HandlerSpec) -> Self {
+        self.handler = Some(Handler::Spec(spec));
+        self
+    }
+
+    /// Sets a closure as the handler of this rule, for what a [`HandlerSpec`] can't express.
+    /// Unlike specs, closures can't be compared or loaded from rule files.
+    ///
+    /// Handlers are run again on the url they return until it doesn't change or was already
+    /// seen, so the closure must not return a new url every time (eg. by appending to it).
+    pub fn with_handler(mut self, handler: impl Fn(Url) -> Url + Sync + Send + 'static) -> Self {
+        self.handler = Some(Handler::Custom(Box::new(handler)));
+        self
+    }
+
+    /// The handler of this rule when it is declarative.
+    pub fn handler_spec(&self) -> Option<&HandlerSpec> {
+        match &self.handler {
+            Some(Handler::Spec(spec)) => Some(spec),
+            _ => None,
+        }
+    }
 }
 
-#[allow(dead_code)]
-#[derive(Clone, Debug)]
-pub(crate) enum M {
+/// A website or app that is reachable from more than one host name.
+///
+/// Rules are written once per service using [`Service::hosts`] so that
+/// short and alternative domains (`youtu.be`, `x.com`, `redd.it` ...)
+/// get the same treatment as the main domain.
+pub(crate) struct Service {
+    /// Host matchers of the main domain and all its aliases.
+    hosts: &'static [M],
+    /// Rewrites a short form url to its canonical form (eg. `youtu.be/ID` to `youtube.com/watch?v=ID`).
+    ///
+    /// Only used when [`Options::expand_short_urls`] is enabled and must not require any network call.
+    /// Returns `None` when the url is not a short form url of this service.
+    expand: Option<fn(&Url) -> Option<Url>>,
+}
+
+impl Service {
+    pub(crate) fn hosts(&self) -> Vec<M> {
+        self.hosts.to_vec()
+    }
+}
+
+/// A matcher, used to match hosts and paths (as `host/path`) of urls and keys of params.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "rule-files",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum M {
+    /// Matches everything.
     Any,
+    /// Matches everything except the given value.
     AllBut(&'static str),
+    /// Matches when all of the values are contained in the input.
     ContainsAll(Vec<&'static str>),
     Exact(&'static str),
     StartsWith(&'static str),
     Contains(&'static str),
+    /// Matches the host (of a `host/path` input) when it is the given domain or any of its subdomains.
+    ///
+    /// Rules that only use this matcher for their host are indexed, see [`RuleSet`].
+    Domain(&'static str),
 }
 
 impl M {
@@ -71,15 +288,23 @@ impl M {
                 M::Any => true,
                 M::Exact(e) => input.eq(e.as_bytes()),
                 M::StartsWith(sw) => input.starts_with(sw.as_bytes()),
-                M::Contains(c) => input.windows(c.len()).any(|w| w.eq(c.as_bytes())),
-                M::ContainsAll(all) => all
-                    .iter()
-                    .map(|a| M::Contains(a))
-                    .collect::<Vec<_>>()
-                    .iter()
-                    .all(|a| a.matches(Some(input))),
+                M::Contains(c) => contains(input, c.as_bytes()),
+                M::ContainsAll(all) => all.iter().all(|c| contains(input, c.as_bytes())),
 
                 M::AllBut(c) => !c.as_bytes().eq(input),
+                M::Domain(d) => {
+                    let host = input
+                        .split(|b| *b == b'/' || *b == b':')
+                        .next()
+                        .unwrap_or_default();
+                    let host = host.strip_suffix(b".").unwrap_or(host);
+                    let d = d.as_bytes();
+
+                    host.eq(d)
+                        || (host.len() > d.len()
+                            && host.ends_with(d)
+                            && host[host.len() - d.len() - 1] == b'.')
+                }
             },
             None => match self {
                 M::Any => true,
@@ -88,27 +313,46 @@ impl M {
                 | M::StartsWith(_)
                 | M::Contains(_)
                 | M::ContainsAll(_)
-                | M::AllBut(_) => false,
+                | M::AllBut(_)
+                | M::Domain(_) => false,
             },
         }
     }
 }
 
+/// `tag` for [`M::Exact`], `utm_*` for [`M::StartsWith`] and `*clid*` for [`M::Contains`].
+impl std::fmt::Display for M {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            M::Any => f.write_str("*"),
+            M::AllBut(c) => write!(f, "!{c}"),
+            M::ContainsAll(all) => write!(f, "*{}*", all.join("*")),
+            M::Exact(e) => f.write_str(e),
+            M::StartsWith(sw) => write!(f, "{sw}*"),
+            M::Contains(c) => write!(f, "*{c}*"),
+            M::Domain(d) => f.write_str(d),
+        }
+    }
+}
+
+fn contains(input: &[u8], value: &[u8]) -> bool {
+    value.is_empty() || input.windows(value.len()).any(|w| w.eq(value))
+}
+
 /// A cleaned URL.
 ///
-///
-/// This is a wrapper around and `Deref` into [`url::Url`] that also overriedes the `ToString`
-/// to prevent extra `=` at the end of the URL when the query string does
-/// not have any value.
-///
-/// eg.`https://example.com/?json` turns to `https://example.com/?json=` when
-/// `ToString` is called on the Url type.
-///
-
+/// This is a wrapper around and `Deref` into [`url::Url`]. The params that are kept
+/// are serialized as they were in the original url, so a param without value stays
+/// without value (`?json`) and values ending with `=` (eg. padded base64) are untouched.
 #[derive(Debug, Clone)]
 pub struct Cleaned {
     result: Url,
     handlers_used: i32,
+    canonical_steps: Vec<CanonicalStep>,
+    normalizations: Vec<Normalization>,
+    heuristic_removals: Vec<HeuristicRemoval>,
+    pii_findings: Vec<PiiFinding>,
+    rule_set_version: RuleSetVersion,
 }
 
 impl std::ops::Deref for Cleaned {
@@ -120,48 +364,131 @@ impl std::ops::Deref for Cleaned {
 }
 
 impl Cleaned {
+    pub(crate) fn new(result: Url) -> Self {
+        Self {
+            result,
+            handlers_used: 0,
+            canonical_steps: vec![],
+            normalizations: vec![],
+            heuristic_removals: vec![],
+            pii_findings: vec![],
+            rule_set_version: RuleSetVersion::default(),
+        }
+    }
+
     pub fn number_of_handlers_used(&self) -> i32 {
         self.handlers_used
     }
+
+    /// The steps of [`Options::canonicalize`] that changed the url.
+    pub fn canonical_steps(&self) -> &[CanonicalStep] {
+        &self.canonical_steps
+    }
+
+    /// How risky it is to open the cleaned url, eg. before redirecting to it.
+    pub fn safety(&self) -> Safety {
+        Safety::classify(&self.result)
+    }
+
+    /// The fixes applied to the input of [`clean_lenient`] before parsing it.
+    pub fn normalizations(&self) -> &[Normalization] {
+        &self.normalizations
+    }
+
+    /// The params removed by [`Options::aggressive`] instead of a rule,
+    /// eg. to show them or let people undo the removal.
+    pub fn heuristic_removals(&self) -> &[HeuristicRemoval] {
+        &self.heuristic_removals
+    }
+
+    /// The version of the [`RuleSet`] the url was cleaned with.
+    pub fn rule_set_version(&self) -> RuleSetVersion {
+        self.rule_set_version
+    }
+
+    /// The params with personal data found by [`Options::pii`], they were removed
+    /// or redacted according to the policy.
+    pub fn pii_findings(&self) -> &[PiiFinding] {
+        &self.pii_findings
+    }
+
+    /// The cleaned url with the [`Cleaned::heuristic_removals`] put back where they were
+    /// in its query.
+    pub fn without_heuristics(&self) -> Url {
+        let mut url = self.result.clone();
+        if !self.heuristic_removals.is_empty() {
+            let mut pairs = url
+                .query()
+                .map(|query| query.split('&').collect::<Vec<_>>())
+                .unwrap_or_default();
+            for removal in &self.heuristic_removals {
+                pairs.insert(removal.index.min(pairs.len()), &removal.pair);
+            }
+            url.set_query(Some(&pairs.join("&")));
+        }
+        url
+    }
 }
 
-impl ToString for Cleaned {
-    fn to_string(&self) -> String {
-        self.result.as_ref().trim_end_matches('=').to_string()
+impl std::fmt::Display for Cleaned {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.result.as_str())
     }
 }
 
+/// Optional behaviours of the cleaner, all of them are disabled by default.
+///
+/// ```rust
+/// let options = tracking_params::Options {
+///     expand_short_urls: true,
+///     ..Default::default()
+/// };
+/// let url = url::Url::parse("https://youtu.be/dQw4w9WgXcQ?si=AbCdEfGh&t=5")?;
+///
+/// assert_eq!(
+///     tracking_params::clean_with(url, &options).to_string(),
+///     "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=5"
+/// );
+/// # Ok::<_, url::ParseError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Rewrite short form urls that can be expanded without a network call
+    /// (eg. `youtu.be/ID` to `youtube.com/watch?v=ID`) to their canonical form.
+    pub expand_short_urls: bool,
+    /// Clean the `http(s)` urls found in param values (eg. the `url` of a share link),
+    /// up to this many levels deep. The outer url is kept and the nested urls are
+    /// escaped the same way they were. `0` disables it.
+    pub nested_urls_depth: usize,
+    /// Rewrite the cleaned url to a canonical form, see [`Canonicalize`].
+    pub canonicalize: Canonicalize,
+    /// Also remove the query params that no rule knows but look like opaque identifiers,
+    /// scored by the shape, length and randomness of their value and whether their key
+    /// looks like a click id (eg. `xyzclid=Cj0KCQiA5rGuBhCnARIs`). Params preserved by
+    /// a rule (see [`Rule::preserving`]) are always kept and the removed ones are listed
+    /// in [`Cleaned::heuristic_removals`].
+    pub aggressive: bool,
+    /// Remove or redact the query and fragment params that contain personal data: email
+    /// addresses (as is, percent-encoded or base64-encoded), phone numbers and hashed
+    /// emails. The params found are listed in [`Cleaned::pii_findings`].
+    pub pii: Option<PiiPolicy>,
+    /// The categories of params to remove, all of them by default.
+    /// Use a [`Preset`] or build a custom set, see [`Categories`].
+    ///
+    /// The handlers of the rules (eg. unwrapping redirects) are run whatever the categories.
+    pub categories: Categories,
+}
+
 /// Removes tracking parameters from a given [`Url`] type.
 ///
 /// This owns the input and returns a [`Cleaned`] type.
 pub fn clean(url: Url) -> Cleaned {
-    let mut handlers_used = 0;
-    // Find applicable rules for this hostname
-    let host_path = format!(
-        "{}/{}",
-        url.host_str().unwrap_or_default().trim_end_matches('/'),
-        url.path()
-    );
-    let matched_rules = rules::GLOBAL_PARAMS
-        .iter()
-        .filter(|r| r.host_path.iter().any(|d| d.matches_str(Some(&host_path))))
-        .collect::<Vec<_>>();
-
-    // Run ths url through any rules that has a handler defined
-    let rules_with_handles = matched_rules.iter().filter(|r| r.handler.is_some());
-
-    let mut url = url;
-    for rule in rules_with_handles {
-        if let Some(handler) = &rule.handler {
-            url = handler(url);
-            handlers_used += 1;
-        }
-    }
+    clean_with(url, &Options::default())
+}
 
-    Cleaned {
-        result: clean_hash_params(clean_query_string(url, &matched_rules), &matched_rules),
-        handlers_used,
-    }
+/// Same as [`clean`] but with the given [`Options`].
+pub fn clean_with(url: Url, options: &Options) -> Cleaned {
+    RuleSet::builtin().clean_with(url, options)
 }
 
 /// Removes tracking parameters from a given string reference that is expected to be a valid URL.
@@ -175,6 +502,81 @@ pub fn clean_str(url: &str) -> Result<String, url::ParseError> {
     Ok(url.to_string())
 }
 
+/// Same as [`clean_str`] but avoids parsing the URL and allocating a new string when it
+/// is already clean.
+///
+/// The params are removed from the input as is, it is only parsed when a rule with a
+/// handler applies. It's returned as [`Cow::Borrowed`] when nothing was removed.
+/// Input that isn't already in the form [`url::Url`] serializes to (eg. `HTTPS://Example.com`)
+/// always takes the slow path and is normalized like [`clean_str`] would.
+///
+/// ```rust
+/// use std::borrow::Cow;
+///
+/// let clean_url = tracking_params::clean_cow("https://example.com/post?id=1")?;
+/// assert!(matches!(clean_url, Cow::Borrowed("https://example.com/post?id=1")));
+///
+/// let clean_url = tracking_params::clean_cow("https://example.com/post?id=1&utm_source=x")?;
+/// assert!(matches!(clean_url, Cow::Owned(_)));
+/// assert_eq!(clean_url, "https://example.com/post?id=1");
+/// # Ok::<_, url::ParseError>(())
+/// ```
+pub fn clean_cow(url: &str) -> Result<Cow<'_, str>, url::ParseError> {
+    RuleSet::builtin().clean_cow(url)
+}
+
+/// Cleans many urls at once, eg. the lines of an export.
+///
+/// The results are in the same order as the input, an url that can't be parsed
+/// gets its own error without affecting the others.
+/// With the `rayon` feature enabled the urls are cleaned in parallel.
+///
+/// ```rust
+/// let results = tracking_params::clean_batch(["https://example.com/?utm_source=x", "not a url"]);
+///
+/// assert_eq!(results[0].as_ref().unwrap().to_string(), "https://example.com/");
+/// assert!(results[1].is_err());
+/// ```
+pub fn clean_batch<I>(urls: I) -> Vec<Result<Cleaned, url::ParseError>>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str> + Send,
+{
+    RuleSet::builtin().clean_batch(urls)
+}
+
+/// Same as [`clean_str_raw`] but also accepts urls the way they are often pasted from text.
+///
+/// Surrounding whitespace, `<...>` or quotes and trailing punctuation (`.`, `,` ...) are removed
+/// and `https://` is added when there is no scheme but the host is a domain
+/// (`twitter.com/x` but never `localhost/x`). The fixes are reported by
+/// [`Cleaned::normalizations`].
+///
+/// ```rust
+/// use tracking_params::{CleanError, Normalization};
+///
+/// let cleaned = tracking_params::clean_lenient(" <twitter.com/x?utm_source=y>.\n")?;
+///
+/// assert_eq!(cleaned.to_string(), "https://twitter.com/x");
+/// assert_eq!(
+///     cleaned.normalizations(),
+///     [
+///         Normalization::TrimmedWhitespace,
+///         Normalization::TrimmedPunctuation,
+///         Normalization::Unwrapped,
+///         Normalization::AddedScheme,
+///     ]
+/// );
+/// assert_eq!(
+///     tracking_params::clean_lenient("localhost/x").unwrap_err(),
+///     CleanError::MissingScheme("localhost".to_string())
+/// );
+/// # Ok::<_, CleanError>(())
+/// ```
+pub fn clean_lenient(url: &str) -> Result<Cleaned, CleanError> {
+    RuleSet::builtin().clean_lenient(url)
+}
+
 /// Same as [`clean_str`] but returns the [`Cleaned`] type
 pub fn clean_str_raw(url: &str) -> Result<Cleaned, url::ParseError> {
     let url = Url::parse(url)?;
@@ -183,58 +585,64 @@ pub fn clean_str_raw(url: &str) -> Result<Cleaned, url::ParseError> {
     Ok(cleaned)
 }
 
-fn clean_query_string(url: Url, rules: &[&Rule]) -> Url {
+/// The `host[:port]/path` part of the url that is matched by [`Rule::host_path`](Rule#structfield.host_path).
+fn host_path(url: &Url) -> &str {
+    &url[Position::BeforeHost..Position::AfterPath]
+}
+
+fn expand_short_url(url: Url) -> Url {
+    let host_path = host_path(&url);
+    let expanded = rules::SERVICES
+        .iter()
+        .filter(|s| s.hosts.iter().any(|h| h.matches_str(Some(host_path))))
+        .find_map(|s| s.expand.and_then(|expand| expand(&url)));
+
+    expanded.unwrap_or(url)
+}
+
+fn clean_query_string(url: Url, rules: &Matched) -> Url {
     let mut url = url;
     if url.query().is_none() {
         return url;
     }
 
-    let queries = url
-        .query_pairs()
-        .into_iter()
-        .filter(|(k, _)| {
-            !rules
-                .iter()
-                .any(|r| r.params.iter().any(|p| p.matches_str(Some(k.as_ref()))))
-        })
-        .map(|(k, v)| (k.to_string(), v.to_string()))
-        .collect::<Vec<_>>();
-
-    url.set_query(None); // clear all queries
-    if queries.is_empty() {
-        return url; // prevents dangling `?` at the end (as a result of `query_pairs_mut` call below)
+    let query = clean_pairs(url.query().unwrap_or_default(), rules).into_owned();
+    if query.is_empty() {
+        url.set_query(None); // prevents dangling `?` at the end
+    } else {
+        url.set_query(Some(&query));
     }
 
-    let mut params = url.query_pairs_mut();
+    url
+}
 
-    for (k, v) in queries {
-        params.append_pair(k.as_ref(), v.as_ref());
+/// Removes the `key=value` pairs of a query string that any of the `rules` removes,
+/// borrowed when nothing was removed.
+///
+/// The pairs that are kept are not re-encoded, `query_pairs_mut` would add a `=`
+/// to pairs without one.
+fn clean_pairs<'a>(pairs: &'a str, rules: &Matched) -> Cow<'a, str> {
+    let keep = |pair: &&str| !pair.is_empty() && !rules.removes(&query_key(pair));
+    if pairs.split('&').all(|pair| keep(&pair)) {
+        return Cow::Borrowed(pairs);
     }
 
-    params.finish().to_owned()
+    Cow::Owned(pairs.split('&').filter(keep).collect::<Vec<_>>().join("&"))
+}
+
+/// The decoded key of a `key=value` pair of a query string.
+fn query_key(pair: &str) -> Cow<'_, str> {
+    url::form_urlencoded::parse(pair.as_bytes())
+        .next()
+        .map(|(k, _)| k)
+        .unwrap_or_default()
 }
 
-fn clean_hash_params(url: Url, rules: &[&Rule]) -> Url {
+fn clean_hash_params(url: Url, rules: &Matched) -> Url {
     let mut url = url;
 
     if let Some(f) = url.fragment() {
-        let mut fr = String::with_capacity(f.len());
-
-        for item in f.split('&') {
-            if let Some(key) = item.split('=').take(1).collect::<Vec<_>>().first() {
-                if !rules
-                    .iter()
-                    .any(|r| r.params.iter().any(|p| p.matches_str(Some(*key))))
-                {
-                    fr.push_str(item);
-                    fr.push('&');
-                }
-            }
-        }
-        if fr.ends_with('&') {
-            fr.remove(fr.len() - 1);
-        }
-
+        let fr = Fragment::parse(f).clean(rules).into_owned();
         if fr.is_empty() {
             url.set_fragment(None); // prevents dangling `#` at the end
         } else {
@@ -265,6 +673,10 @@ mod tests {
         "https://twitter.com/elonmusk/status/1608273870901096454?ref_src=twsrc%5Etfw&from=home",
         "https://twitter.com/elonmusk/status/1608273870901096454?from=home"; "twitter: good & bad query"
     )]
+    #[test_case(
+        "https://twitter.com./x?ref_src=twsrc",
+        "https://twitter.com./x"; "twitter: fully qualified host"
+    )]
     //
     // Query without value
     //
@@ -303,6 +715,38 @@ mod tests {
         "https://twitter.com/elonmusk/status/1608273870901096454#ref_src=twsrc%5Etfw&from=home",
         "https://twitter.com/elonmusk/status/1608273870901096454#from=home"; "twitter: good & bad hash param"
     )]
+    #[test_case(
+        "https://example.com/#/products?id=1&utm_source=x",
+        "https://example.com/#/products?id=1"; "vue: hash route with query"
+    )]
+    #[test_case(
+        "https://example.com/app/#/products/12?utm_source=x&fbclid=abc",
+        "https://example.com/app/#/products/12"; "vue: hash route with only trackers"
+    )]
+    #[test_case(
+        "https://example.com/#!/products?gclid=abc&page=2",
+        "https://example.com/#!/products?page=2"; "angular: hashbang route with query"
+    )]
+    #[test_case(
+        "https://example.com/#/products/ref=2?page=2",
+        "https://example.com/#/products/ref=2?page=2"; "angular: route path is kept"
+    )]
+    #[test_case(
+        "https://example.com/post?utm_source=x#:~:text=tracking%20params",
+        "https://example.com/post#:~:text=tracking%20params"; "chrome: text fragment"
+    )]
+    #[test_case(
+        "https://example.com/post#ref=1:~:text=start,end&text=other",
+        "https://example.com/post#ref=1:~:text=start,end&text=other"; "chrome: text fragment with anchor"
+    )]
+    #[test_case(
+        "https://example.com/post#section=2",
+        "https://example.com/post#section=2"; "anchor with eq ="
+    )]
+    #[test_case(
+        "https://example.com/post#ref",
+        "https://example.com/post#ref"; "anchor"
+    )]
     fn hash(input: &str, expected: &str) {
         test_common(input, expected)
     }
@@ -332,6 +776,26 @@ mod tests {
         "https://whatsmyreferer.com/?json",
         "https://whatsmyreferer.com/?json"; "misc: no trailing eq ="
     )]
+    #[test_case(
+        "https://whatsmyreferer.com/?json&utm_source=x&format=",
+        "https://whatsmyreferer.com/?json&format="; "misc: empty value keeps its eq ="
+    )]
+    #[test_case(
+        "https://example.com/verify?token=YWJjZA==&utm_source=x",
+        "https://example.com/verify?token=YWJjZA=="; "misc: padded base64 value"
+    )]
+    #[test_case(
+        "https://example.com/verify?utm_source=x&token=YWJjZGU=",
+        "https://example.com/verify?token=YWJjZGU="; "misc: padded base64 value at the end"
+    )]
+    #[test_case(
+        "https://example.com/verify?token=YWJj%3D%3D&q=a+b%20c",
+        "https://example.com/verify?token=YWJj%3D%3D&q=a+b%20c"; "misc: keeps original escaping"
+    )]
+    #[test_case(
+        "https://example.com/verify#fbclid=1&token=YWJjZA==",
+        "https://example.com/verify#token=YWJjZA=="; "misc: padded base64 value in hash"
+    )]
     fn misc(input: &str, expected: &str) {
         test_common(input, expected)
     }
@@ -380,6 +844,762 @@ mod tests {
         test_common(input, expected)
     }
 
+    #[test_case(
+        "https://www.google.com/search?q=rust&sxsrf=ALiCzsZ2Y3&oq=rust&aqs=chrome..69i57j0i512l9.1234j0j7&sourceid=chrome&ie=UTF-8&sca_esv=559383000",
+        "https://www.google.com/search?q=rust"; "google: search session state"
+    )]
+    #[test_case(
+        "https://www.google.co.uk/search?q=rust&tbm=nws&tbs=qdr%3Aw&hl=en&ei=abc&ved=0ahUKEwj&client=firefox-b-d&biw=1280&bih=720",
+        "https://www.google.co.uk/search?q=rust&tbm=nws&tbs=qdr%3Aw&hl=en"; "google: keeps filters"
+    )]
+    #[test_case(
+        "https://www.bing.com/search?q=rust&form=QBLH&sp=-1&ghc=1&lq=0&pq=rust&sc=10-4&qs=n&sk=&cvid=6C1F&ghsh=0&ghacc=0&ghpl=",
+        "https://www.bing.com/search?q=rust"; "bing: search session state"
+    )]
+    #[test_case(
+        "https://duckduckgo.com/?q=rust&t=h_&atb=v350-1&ia=web",
+        "https://duckduckgo.com/?q=rust&ia=web"; "duckduckgo: partner and cohort"
+    )]
+    #[test_case(
+        "https://duckduckgo.com/l/?uddg=https%3A%2F%2Fwww.rust-lang.org%2F&rut=4fa1c0",
+        "https://www.rust-lang.org/"; "duckduckgo: extract from uddg"
+    )]
+    #[test_case(
+        "https://yandex.ru/search/?text=rust&lr=213&clid=2270455&win=500&src=suggest_B",
+        "https://yandex.ru/search/?text=rust"; "yandex: search session state"
+    )]
+    #[test_case(
+        "https://yandex.ru/clck/jsredir?url=https%3A%2F%2Fwww.rust-lang.org%2F&uuid=&state=abc",
+        "https://www.rust-lang.org/"; "yandex: jsredir with decodable url"
+    )]
+    #[test_case(
+        "https://www.baidu.com/s?wd=rust&rsv_spt=1&rsv_iqid=0xd4&issp=1&f=8&rsv_bp=1&rsv_idx=2&ie=utf-8&tn=baiduhome_pg&rsv_enter=1&rsv_dl=tb&inputT=1234",
+        "https://www.baidu.com/s?wd=rust"; "baidu: search session state"
+    )]
+    #[test_case(
+        "https://www.baidu.com/link?url=https%3A%2F%2Fwww.rust-lang.org%2F&eqid=e0b5",
+        "https://www.rust-lang.org/"; "baidu: link with decodable url"
+    )]
+    #[test_case(
+        "https://www.baidu.com/link?url=Xv3b0pQkq2&eqid=e0b5",
+        "https://www.baidu.com/link?url=Xv3b0pQkq2"; "baidu: link with opaque url"
+    )]
+    #[test_case(
+        "https://www.ecosia.org/search?method=index&q=rust&tt=mzl&addon=firefox&addonversion=5.0.0&tts=st_asaf",
+        "https://www.ecosia.org/search?method=index&q=rust"; "ecosia: search session state"
+    )]
+    fn search_engines(input: &str, expected: &str) {
+        test_common(input, expected)
+    }
+
+    #[test_case(
+        "https://www.amazon.co.uk/Rust-Programming-Language-2nd/dp/1718503105/ref=sr_1_1?crid=2Q9W&keywords=rust&qid=1690000000&sprefix=rust%2Caps%2C80&sr=8-1&th=1",
+        "https://www.amazon.co.uk/Rust-Programming-Language-2nd/dp/1718503105/ref=sr_1_1?keywords=rust&th=1"; "amazon: keeps product path"
+    )]
+    #[test_case(
+        "https://www.ebay.co.uk/itm/125722837394?var=426635416042&_trkparms=amclksrc%3DITM&_trksid=p2047675.c100005.m1851&amdata=enc%3A1",
+        "https://www.ebay.co.uk/itm/125722837394?var=426635416042"; "ebay: keeps item and variation"
+    )]
+    #[test_case(
+        "https://www.aliexpress.com/item/1005005093341234.html?spm=a2g0o.home.0.0.650c2145&pdp_npi=3%40dis%21GBP&algo_pvid=9b5a&algo_exp_id=9b5a-0&aff_fcid=f00&sku_id=12000031",
+        "https://www.aliexpress.com/item/1005005093341234.html?sku_id=12000031"; "aliexpress: keeps item and sku"
+    )]
+    #[test_case(
+        "https://item.taobao.com/item.htm?spm=a21bo.jianhua.201876.1&scm=1007.40986.275655.0&id=672410102233&pvid=2d0a",
+        "https://item.taobao.com/item.htm?id=672410102233"; "taobao: keeps id"
+    )]
+    #[test_case(
+        "https://www.etsy.com/uk/listing/1234567890/handmade-mug?click_key=abc&click_sum=d7&ref=hp_rv-1&pro=1&frs=1&variation0=2871",
+        "https://www.etsy.com/uk/listing/1234567890/handmade-mug?variation0=2871"; "etsy: keeps listing and variation"
+    )]
+    #[test_case(
+        "https://www.walmart.com/ip/Lodge-Cast-Iron-Skillet/10292375?athcpid=10292375&athpgid=AthenaHomepageDesktop&athznid=ItemCarousel&selected=true",
+        "https://www.walmart.com/ip/Lodge-Cast-Iron-Skillet/10292375?selected=true"; "walmart: keeps item"
+    )]
+    #[test_case(
+        "https://linen-co.myshopify.com/products/linen-shirt?variant=40123456789&_pos=1&_sid=8f3c2&_ss=r&_psq=linen",
+        "https://linen-co.myshopify.com/products/linen-shirt?variant=40123456789"; "shopify: keeps variant"
+    )]
+    #[test_case(
+        "https://linen-co.myshopify.com/products/linen-shirt?pr_prod_strat=collection_fallback&pr_rec_id=9c2&pr_rec_pid=712&pr_ref_pid=713&pr_seq=uniform",
+        "https://linen-co.myshopify.com/products/linen-shirt"; "shopify: product recommendations"
+    )]
+    #[test_case(
+        "https://www.nike.com/products/shoe?_pos=1&_sid=abc&color=red",
+        "https://www.nike.com/products/shoe?_pos=1&_sid=abc&color=red"; "shopify: other sites with a products path"
+    )]
+    fn ecommerce(input: &str, expected: &str) {
+        test_common(input, expected)
+    }
+
+    #[test_case(
+        "https://www.tiktok.com/@rustlang/video/7260000000000000000?_r=1&_t=8eXyzAbc&is_from_webapp=1&sender_device=pc&is_copy_url=1&lang=en",
+        "https://www.tiktok.com/@rustlang/video/7260000000000000000?lang=en"; "tiktok: share link"
+    )]
+    #[test_case(
+        "https://www.facebook.com/share/p/1AbCdEfGh/?mibextid=WC7FNe",
+        "https://www.facebook.com/share/p/1AbCdEfGh/"; "facebook: share link"
+    )]
+    #[test_case(
+        "https://www.facebook.com/groups/123/posts/456/?__cft__%5B0%5D=AZXabc&__tn__=%2CO%2CP-R",
+        "https://www.facebook.com/groups/123/posts/456/"; "facebook: click tracking arrays"
+    )]
+    #[test_case(
+        "https://www.linkedin.com/in/someone?trk=public_profile_browsemap&lipi=urn%3Ali%3Apage%3Ad_flagship3&refId=ab12&trackingId=cd34",
+        "https://www.linkedin.com/in/someone"; "linkedin: profile link"
+    )]
+    #[test_case(
+        "https://www.threads.net/@rustlang/post/CuP48CiS5sx?xmt=AQGzAbc&slof=1",
+        "https://www.threads.net/@rustlang/post/CuP48CiS5sx"; "threads: share link"
+    )]
+    #[test_case(
+        "https://x.com/rustlang/status/1700000000000000000?s=20&t=AbCdEfGh",
+        "https://x.com/rustlang/status/1700000000000000000"; "x: share link"
+    )]
+    #[test_case(
+        "https://box.com/s/abc?s=20",
+        "https://box.com/s/abc?s=20"; "x: does not match other domains ending with x.com"
+    )]
+    #[test_case(
+        "https://www.twitch.tv/videos/1900000000?t=1h2m&tt_content=vod&tt_medium=mobile_web_share&sr=a",
+        "https://www.twitch.tv/videos/1900000000?t=1h2m"; "twitch: share link"
+    )]
+    #[test_case(
+        "https://vimeo.com/123456789?fl=pl&fe=sh&share=copy&h=ab12cd",
+        "https://vimeo.com/123456789?h=ab12cd"; "vimeo: keeps unlisted hash"
+    )]
+    #[test_case(
+        "https://www.pinterest.com/pin/1234567890/?epik=dj0yJnU9&mweb_unauth_id=ab12&sender=123&invite_code=cd34",
+        "https://www.pinterest.com/pin/1234567890/"; "pinterest: share link"
+    )]
+    #[test_case(
+        "https://www.instagram.com/reel/CzAbCdEf/?igsh=MWQ1ZGUxMzBkMA%3D%3D",
+        "https://www.instagram.com/reel/CzAbCdEf/"; "instagram: share link"
+    )]
+    #[test_case(
+        "https://www.youtube.com/watch?v=dQw4w9WgXcQ&si=AbCdEfGh&t=42",
+        "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42"; "youtube: share link"
+    )]
+    #[test_case(
+        "https://www.reddit.com/r/rust/comments/15abc/title/?share_id=AbCd&utm_content=2&utm_medium=android_app&utm_source=share&utm_term=1",
+        "https://www.reddit.com/r/rust/comments/15abc/title/"; "reddit: share link"
+    )]
+    #[test_case(
+        "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC?si=ab12cd34&nd=1&dl_branch=1",
+        "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC"; "spotify: share link"
+    )]
+    fn social(input: &str, expected: &str) {
+        test_common(input, expected)
+    }
+
+    #[test_case(
+        "https://youtu.be/dQw4w9WgXcQ?si=AbCdEfGh&t=42",
+        "https://youtu.be/dQw4w9WgXcQ?t=42"; "youtube: youtu.be"
+    )]
+    #[test_case(
+        "https://amzn.eu/d/4bCdEfG?tag=affiliate-21&linkCode=ll1",
+        "https://amzn.eu/d/4bCdEfG"; "amazon: amzn.eu"
+    )]
+    #[test_case(
+        "https://a.co/d/4bCdEfG?tag=affiliate-21",
+        "https://a.co/d/4bCdEfG"; "amazon: a.co"
+    )]
+    #[test_case(
+        "https://redd.it/15abc?share_id=AbCd",
+        "https://redd.it/15abc"; "reddit: redd.it"
+    )]
+    #[test_case(
+        "https://spotify.link/AbCdEfGh?si=ab12cd34",
+        "https://spotify.link/AbCdEfGh"; "spotify: spotify.link"
+    )]
+    #[test_case(
+        "https://fb.watch/mAbCdEf/?mibextid=WC7FNe",
+        "https://fb.watch/mAbCdEf/"; "facebook: fb.watch"
+    )]
+    #[test_case(
+        "https://fbx.watch/mAbCdEf/?mibextid=WC7FNe",
+        "https://fbx.watch/mAbCdEf/?mibextid=WC7FNe"; "facebook: does not match other domains"
+    )]
+    fn service_aliases(input: &str, expected: &str) {
+        test_common(input, expected)
+    }
+
+    #[test_case(
+        "https://youtu.be/dQw4w9WgXcQ?t=5",
+        "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=5"; "youtube: youtu.be with query"
+    )]
+    #[test_case(
+        "https://youtu.be/dQw4w9WgXcQ?si=AbCdEfGh",
+        "https://www.youtube.com/watch?v=dQw4w9WgXcQ"; "youtube: youtu.be with tracker only"
+    )]
+    #[test_case(
+        "https://youtu.be/",
+        "https://youtu.be/"; "youtube: youtu.be without id"
+    )]
+    #[test_case(
+        "https://redd.it/15abc",
+        "https://www.reddit.com/comments/15abc"; "reddit: redd.it"
+    )]
+    #[test_case(
+        "https://instagr.am/p/CzAbCdEf/?igsh=MWQ1",
+        "https://www.instagram.com/p/CzAbCdEf/"; "instagram: instagr.am"
+    )]
+    #[test_case(
+        "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        "https://www.youtube.com/watch?v=dQw4w9WgXcQ"; "youtube: canonical url is unchanged"
+    )]
+    fn expand_short_urls(input: &str, expected: &str) {
+        let options = Options {
+            expand_short_urls: true,
+            ..Default::default()
+        };
+        let result = clean_with(Url::parse(input).unwrap(), &options).to_string();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test_case(
+        "https://twitter.com/intent/tweet?text=Hi&url=https%3A%2F%2Fexample.com%2Fpost%3Fid%3D1%26utm_source%3Dtwitter",
+        "https://twitter.com/intent/tweet?text=Hi&url=https%3A%2F%2Fexample.com%2Fpost%3Fid%3D1"; "share link"
+    )]
+    #[test_case(
+        "https://app.example.com/login?return_to=https://example.com/account?fbclid=abc",
+        "https://app.example.com/login?return_to=https://example.com/account"; "unescaped value"
+    )]
+    #[test_case(
+        "https://app.example.com/login?return_to=https%3a%2f%2fexample.com%2f%3ftab%3d2%26gclid%3dabc&utm_source=x",
+        "https://app.example.com/login?return_to=https%3a%2f%2fexample.com%2f%3ftab%3d2"; "lowercase escapes"
+    )]
+    #[test_case(
+        "https://example.com/#/login?next=https%3A%2F%2Fexample.com%2F%3Futm_medium%3Demail",
+        "https://example.com/#/login?next=https%3A%2F%2Fexample.com%2F"; "hash route"
+    )]
+    #[test_case(
+        "https://a.com/?u=https%3A%2F%2Fb.com%2F%3Fu%3Dhttps%253A%252F%252Fc.com%252F%253Ffbclid%253D1",
+        "https://a.com/?u=https%3A%2F%2Fb.com%2F%3Fu%3Dhttps%253A%252F%252Fc.com%252F"; "two levels"
+    )]
+    #[test_case(
+        "https://a.com/?u=https%3A%2F%2Fb.com%2F%3Fu%3Dhttps%253A%252F%252Fc.com%252F%253Fu%253Dhttps%25253A%25252F%25252Fd.com%25252F%25253Ffbclid%25253D1",
+        "https://a.com/?u=https%3A%2F%2Fb.com%2F%3Fu%3Dhttps%253A%252F%252Fc.com%252F%253Fu%253Dhttps%25253A%25252F%25252Fd.com%25252F%25253Ffbclid%25253D1"; "depth limit"
+    )]
+    #[test_case(
+        "https://example.com/?next=%2Faccount%3Fref%3Dnav&q=https",
+        "https://example.com/?next=%2Faccount%3Fref%3Dnav&q=https"; "not an url"
+    )]
+    fn nested_urls(input: &str, expected: &str) {
+        let options = Options {
+            nested_urls_depth: 2,
+            ..Default::default()
+        };
+        let result = clean_with(Url::parse(input).unwrap(), &options).to_string();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test_case(
+        "https://M.YouTube.com:443/watch?v=dQw4w9WgXcQ&t=5&feature=share",
+        "https://www.youtube.com/watch?t=5&v=dQw4w9WgXcQ",
+        &[CanonicalStep::SortParams, CanonicalStep::DesktopHost]; "youtube: mobile and order insensitive"
+    )]
+    #[test_case(
+        "https://example.com/a%2fb?b=1&a=%3a#",
+        "https://example.com/a%2Fb?b=1&a=%3A",
+        &[CanonicalStep::UppercasePercentEncoding]; "params order is kept"
+    )]
+    #[test_case(
+        "https://en.m.wikipedia.org/wiki/Rust?",
+        "https://en.wikipedia.org/wiki/Rust",
+        &[CanonicalStep::DesktopHost]; "wikipedia: mobile subdomain"
+    )]
+    #[test_case(
+        "foo://Example.COM:21/a",
+        "foo://example.com:21/a",
+        &[CanonicalStep::Lowercase]; "other scheme"
+    )]
+    #[test_case(
+        "ftp://example.com:21/a",
+        "ftp://example.com/a",
+        &[]; "default port is removed by parsing"
+    )]
+    fn canonicalize(input: &str, expected: &str, steps: &[CanonicalStep]) {
+        let options = Options {
+            canonicalize: Canonicalize::all(),
+            ..Default::default()
+        };
+        let result = clean_with(Url::parse(input).unwrap(), &options);
+
+        assert_eq!(result.to_string(), expected);
+        assert_eq!(result.canonical_steps(), steps);
+    }
+
+    #[test]
+    fn canonicalize_steps_are_switchable() {
+        let url = Url::parse("https://m.youtube.com/watch?v=dQw4w9WgXcQ&t=5").unwrap();
+        let options = Options {
+            canonicalize: Canonicalize {
+                desktop_host: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = clean_with(url.clone(), &options);
+
+        assert_eq!(
+            result.to_string(),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=5"
+        );
+        assert_eq!(result.canonical_steps(), [CanonicalStep::DesktopHost]);
+        assert!(clean(url).canonical_steps().is_empty());
+    }
+
+    #[test_case(
+        "twitter.com/x?utm_source=y",
+        "https://twitter.com/x",
+        &[Normalization::AddedScheme]; "no scheme"
+    )]
+    #[test_case(
+        "twitter.com/intent/tweet?url=https://example.com/?utm_source=x",
+        "https://twitter.com/intent/tweet?url=https://example.com/?utm_source=x",
+        &[Normalization::AddedScheme]; "no scheme with a url in the query"
+    )]
+    #[test_case(
+        "www.example.com:8080",
+        "https://www.example.com:8080/",
+        &[Normalization::AddedScheme]; "no scheme with port"
+    )]
+    #[test_case(
+        "192.168.0.1/admin?utm_source=y",
+        "https://192.168.0.1/admin",
+        &[Normalization::AddedScheme]; "no scheme with ip address"
+    )]
+    #[test_case(
+        "\n\t https://example.com/?utm_source=y \r\n",
+        "https://example.com/",
+        &[Normalization::TrimmedWhitespace]; "whitespace"
+    )]
+    #[test_case(
+        "\"https://example.com/a?fbclid=1\"",
+        "https://example.com/a",
+        &[Normalization::Unwrapped]; "quotes"
+    )]
+    #[test_case(
+        "“https://example.com/a”",
+        "https://example.com/a",
+        &[Normalization::Unwrapped]; "curly quotes"
+    )]
+    #[test_case(
+        "https://example.com/a?id=1),",
+        "https://example.com/a?id=1",
+        &[Normalization::TrimmedPunctuation]; "trailing punctuation"
+    )]
+    #[test_case(
+        "https://en.wikipedia.org/wiki/Rust_(programming_language).",
+        "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+        &[Normalization::TrimmedPunctuation]; "balanced parenthesis"
+    )]
+    #[test_case(
+        "mailto:someone@example.com",
+        "mailto:someone@example.com",
+        &[]; "other scheme"
+    )]
+    fn lenient(input: &str, expected: &str, normalizations: &[Normalization]) {
+        let result = clean_lenient(input).unwrap();
+
+        assert_eq!(result.to_string(), expected);
+        assert_eq!(result.normalizations(), normalizations);
+    }
+
+    #[test_case(" \n", CleanError::Empty; "empty")]
+    #[test_case("<>", CleanError::Empty; "empty wrapper")]
+    #[test_case("localhost/x?utm_source=y", CleanError::MissingScheme("localhost".to_string()); "host without dot")]
+    #[test_case("intranet", CleanError::MissingScheme("intranet".to_string()); "single word")]
+    #[test_case("3.14", CleanError::MissingScheme("3.14".to_string()); "number")]
+    #[test_case("1.2.3", CleanError::MissingScheme("1.2.3".to_string()); "not an ip address")]
+    #[test_case("e.g", CleanError::MissingScheme("e.g".to_string()); "single letter tld")]
+    #[test_case("v1.2/notes", CleanError::MissingScheme("v1.2".to_string()); "numeric tld")]
+    #[test_case(
+        "<https://example.com:99999/>",
+        CleanError::InvalidUrl {
+            source: url::ParseError::InvalidPort,
+            normalizations: vec![Normalization::Unwrapped],
+        }; "invalid url"
+    )]
+    fn lenient_errors(input: &str, expected: CleanError) {
+        assert_eq!(clean_lenient(input).unwrap_err(), expected);
+    }
+
+    #[test_case(
+        "mailto:?subject=Look&body=Hi%2C%20see%20https%3A%2F%2Fexample.com%2Fpost%3Fid%3D1%26utm_source%3Dshare.%20Bye",
+        "mailto:?subject=Look&body=Hi%2C%20see%20https%3A%2F%2Fexample.com%2Fpost%3Fid%3D1.%20Bye"; "mailto: url in body"
+    )]
+    #[test_case(
+        "mailto:someone@example.com?body=https://example.com/a?fbclid=1%20and%20https://example.com/b",
+        "mailto:someone@example.com?body=https://example.com/a%20and%20https://example.com/b"; "mailto: several urls"
+    )]
+    #[test_case(
+        "mailto:someone@example.com?subject=utm_source&body=https%3A%2F%2Fexample.com",
+        "mailto:someone@example.com?subject=utm_source&body=https%3A%2F%2Fexample.com"; "mailto: nothing to clean"
+    )]
+    #[test_case(
+        "intent://scan/#Intent;scheme=zxing;package=com.google.zxing.client.android;S.browser_fallback_url=https%3A%2F%2Fzxing.org%2F%3Futm_source%3Dapp;end",
+        "intent://scan/#Intent;scheme=zxing;package=com.google.zxing.client.android;S.browser_fallback_url=https%3A%2F%2Fzxing.org%2F;end"; "intent: browser fallback url"
+    )]
+    #[test_case(
+        "intent://scan/?utm_source=x#Intent;scheme=zxing;S.browser_fallback_url=https://zxing.org/?id=1&fbclid=2;end",
+        "intent://scan/?utm_source=x#Intent;scheme=zxing;S.browser_fallback_url=https://zxing.org/?id=1;end"; "intent: unescaped browser fallback url"
+    )]
+    #[test_case(
+        "intent://scan/#Intent;scheme=zxing;package=com.google.zxing.client.android;end",
+        "intent://scan/#Intent;scheme=zxing;package=com.google.zxing.client.android;end"; "intent: without fallback"
+    )]
+    #[test_case(
+        "market://details?id=com.example.app&referrer=utm_source%3Dnewsletter%26utm_medium%3Demail",
+        "market://details?id=com.example.app"; "market: referrer"
+    )]
+    #[test_case(
+        "market://details?referrer=utm_source%3Dnewsletter",
+        "market://details"; "market: only referrer"
+    )]
+    #[test_case(
+        "wss://example.com/socket?token=1&utm_source=x",
+        "wss://example.com/socket?token=1"; "websocket"
+    )]
+    #[test_case(
+        "slack://channel?team=T1&id=C1&utm_source=x",
+        "slack://channel?team=T1&id=C1&utm_source=x"; "unknown scheme"
+    )]
+    #[test_case(
+        "ftp://example.com/file?utm_source=x#ref=1",
+        "ftp://example.com/file"; "ftp"
+    )]
+    fn other_schemes(input: &str, expected: &str) {
+        test_common(input, expected)
+    }
+
+    #[test_case("https://example.com/?utm_source=x", &[]; "safe")]
+    #[test_case("mailto:someone@example.com", &[]; "app scheme")]
+    #[test_case(
+        "javascript:alert(document.cookie)",
+        &["The link runs code or shows content that is not from a website"]; "javascript"
+    )]
+    #[test_case(
+        "data:text/html;base64,PHNjcmlwdD4=",
+        &["The link runs code or shows content that is not from a website"]; "data"
+    )]
+    #[test_case(
+        "https://paypal.com@evil.example/",
+        &["The link contains a user name, the website is the part after `@`"]; "userinfo"
+    )]
+    #[test_case(
+        "http://[::1]:8080/",
+        &[
+            "The link points to an IP address instead of a domain name",
+            "The link uses a non standard port",
+        ]; "ip and port"
+    )]
+    #[test_case(
+        "https://paypal.com.login.secure.verify.evil.example/",
+        &["The domain name has an unusual number of subdomains"]; "subdomains"
+    )]
+    #[test_case("https://en.m.wikipedia.org:443/", &[]; "default port")]
+    #[test_case(
+        "https://xn--pple-43d.com/",
+        &[
+            "The domain name imitates `apple.com`",
+            "The domain name mixes characters of different scripts",
+        ]; "idn: mixed script"
+    )]
+    #[test_case(
+        "https://xn--n1aahb.com/",
+        &["The domain name imitates `coco.com`"]; "idn: whole script"
+    )]
+    #[test_case("https://xn--mnchen-3ya.de/", &[]; "idn: not confusable")]
+    #[test_case("https://xn--fiqs8s.cn/", &[]; "idn: single script")]
+    #[test_case("https://xn--tokyo-w91hq39l.jp/", &[]; "idn: han and latin")]
+    #[test_case("https://xn--sony-fo4c6cxq.jp/", &[]; "idn: kana and latin")]
+    #[test_case("https://xn--galaxy-oh5z00a.kr/", &[]; "idn: hangul and latin")]
+    #[test_case("https://xn--my--gm9di64i.jp/", &[]; "idn: hyphen")]
+    #[test_case(
+        "https://xn--abc-2wczrf2ca.gr/",
+        &["The domain name mixes characters of different scripts"]; "idn: greek and latin"
+    )]
+    fn safety(input: &str, warnings: &[&str]) {
+        let safety = clean(Url::parse(input).unwrap()).safety();
+
+        let found = safety.warnings();
+        assert_eq!(
+            found.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            warnings
+        );
+        assert_eq!(safety.is_safe(), warnings.is_empty());
+    }
+
+    #[test_case(
+        "https://example.com/article?id=42&xyzclid=Cj0KCQiA5rGuBhCnARIs&section=home",
+        "https://example.com/article?id=42&section=home",
+        &["xyzclid"]; "unknown click id"
+    )]
+    #[test_case(
+        "https://example.com/?utm_source=x&visitor_id=f47ac10b-58cc-4372-a567-0e02b2c3d479",
+        "https://example.com/",
+        &["visitor_id"]; "only heuristic removals are reported"
+    )]
+    #[test_case(
+        "https://example.com/?zx=Cj0KCQiA5rGuBhCnARIsAN11vgTzXq8ePwLm3&campaign=summer-sale-2024",
+        "https://example.com/?campaign=summer-sale-2024",
+        &["zx"]; "random value"
+    )]
+    #[test_case(
+        "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG",
+        "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG",
+        &[]; "preserved by rule"
+    )]
+    #[test_case(
+        "https://example.com/reset?token=Cj0KCQiA5rGuBhCnARIsAN11vgTzXq8ePwLm3",
+        "https://example.com/reset?token=Cj0KCQiA5rGuBhCnARIsAN11vgTzXq8ePwLm3",
+        &[]; "functional key"
+    )]
+    fn aggressive(input: &str, expected: &str, removed: &[&str]) {
+        let options = Options {
+            aggressive: true,
+            ..Default::default()
+        };
+        let input = Url::parse(input).unwrap();
+        let result = clean_with(input.clone(), &options);
+        let keys = result
+            .heuristic_removals()
+            .iter()
+            .map(|r| r.key.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(result.to_string(), expected);
+        assert_eq!(keys, removed);
+        // The removed params are put back where they were.
+        assert_eq!(
+            result.without_heuristics(),
+            clean_with(input, &Options::default()).result
+        );
+    }
+
+    #[test_case(
+        "https://news.example.com/offer?email=jane%40corp.com&id=42",
+        PiiPolicy::Remove,
+        "https://news.example.com/offer?id=42",
+        &[(PiiCategory::Email, false)]; "percent-encoded email"
+    )]
+    #[test_case(
+        "https://news.example.com/offer?u=amFuZUBjb3JwLmNvbQ%3D%3D&phone=%2B14155552671",
+        PiiPolicy::Redact,
+        "https://news.example.com/offer?u=REDACTED&phone=REDACTED",
+        &[(PiiCategory::Email, false), (PiiCategory::Phone, false)]; "base64 email and phone"
+    )]
+    #[test_case(
+        "https://app.example.com/#/welcome?hem=3f1d6a8f4b2c9e7d5a0b1c2d3e4f5a6b&step=2",
+        PiiPolicy::Remove,
+        "https://app.example.com/#/welcome?step=2",
+        &[(PiiCategory::HashedEmail, true)]; "hashed email in hash route"
+    )]
+    #[test_case(
+        "https://example.com/contact?to=jane@corp.com",
+        PiiPolicy::Remove,
+        "https://example.com/contact",
+        &[(PiiCategory::Email, false)]; "only param"
+    )]
+    #[test_case(
+        "https://example.com/search?q=rust&page=2",
+        PiiPolicy::Remove,
+        "https://example.com/search?q=rust&page=2",
+        &[]; "no pii"
+    )]
+    fn pii(input: &str, policy: PiiPolicy, expected: &str, found: &[(PiiCategory, bool)]) {
+        let options = Options {
+            pii: Some(policy),
+            ..Default::default()
+        };
+        let result = clean_with(Url::parse(input).unwrap(), &options);
+        let findings = result
+            .pii_findings()
+            .iter()
+            .map(|f| (f.category, f.in_fragment))
+            .collect::<Vec<_>>();
+
+        assert_eq!(result.to_string(), expected);
+        assert_eq!(findings, found);
+    }
+
+    #[test_case(
+        "https://www.amazon.com/dp/B08N5WRWNW?tag=blog-20&qid=1&psc=1&ref=sr_1_1",
+        Preset::Safe.into(),
+        "https://www.amazon.com/dp/B08N5WRWNW?tag=blog-20&qid=1&psc=1"; "safe keeps affiliate, session and functional"
+    )]
+    #[test_case(
+        "https://www.amazon.com/dp/B08N5WRWNW?tag=blog-20&qid=1&psc=1&ref=sr_1_1",
+        Preset::Standard.into(),
+        "https://www.amazon.com/dp/B08N5WRWNW?psc=1"; "standard keeps functional"
+    )]
+    #[test_case(
+        "https://www.amazon.com/dp/B08N5WRWNW?tag=blog-20&qid=1&psc=1&ref=sr_1_1",
+        Preset::Strict.into(),
+        "https://www.amazon.com/dp/B08N5WRWNW"; "strict"
+    )]
+    #[test_case(
+        "https://shop.example/?utm_source=x&gclid=1&mc_eid=2",
+        [Category::ClickId].into_iter().collect(),
+        "https://shop.example/?utm_source=x&mc_eid=2"; "custom set"
+    )]
+    #[test_case(
+        "https://www.google.com/url?q=https://example.com/&usg=1",
+        Categories::all().without(Category::ClickId),
+        "https://example.com/"; "handlers run whatever the categories"
+    )]
+    #[test_case(
+        "https://www.amazon.com/gp/r.html?U=https%3A%2F%2Fwww.amazon.com%2Fdp%2FB08N5WRWNW%3Ftag%3Dblog-20",
+        Categories::all().without(Category::Analytics),
+        "https://www.amazon.com/dp/B08N5WRWNW"; "redirect unwrapped without analytics"
+    )]
+    fn categories(input: &str, categories: Categories, expected: &str) {
+        let options = Options {
+            categories,
+            ..Default::default()
+        };
+        let result = clean_with(Url::parse(input).unwrap(), &options);
+
+        assert_eq!(result.to_string(), expected);
+    }
+
+    #[test]
+    fn builtin_rules_have_metadata() {
+        let rules = RuleSet::builtin();
+        let mut ids = rules
+            .rules()
+            .iter()
+            .map(|r| r.meta().id)
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+
+        assert_eq!(ids.len(), rules.len(), "ids are unique");
+        assert!(rules
+            .rules()
+            .iter()
+            .all(|r| !r.meta().id.is_empty() && !r.meta().added_in.is_empty()));
+
+        let cleaned = clean_str_raw("https://example.com/?utm_source=x").unwrap();
+        assert_eq!(cleaned.rule_set_version(), rules.version());
+        assert_eq!(
+            cleaned.rule_set_version().version,
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+
+    #[test]
+    fn builtin_rules_have_no_lint_errors() {
+        let errors = RuleSet::builtin()
+            .lint()
+            .into_iter()
+            .filter(|lint| lint.kind.is_error())
+            .map(|lint| lint.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(errors, Vec::<String>::new());
+    }
+
+    #[test]
+    fn builtin_rules_pass_their_examples() {
+        let failures = match RuleSet::builtin().verify() {
+            Ok(()) => vec![],
+            Err(failures) => failures.iter().map(|f| f.to_string()).collect(),
+        };
+
+        assert_eq!(failures, Vec::<String>::new());
+    }
+
+    #[test_case("https://example.com/my-post?id=12456#comments"; "no trackers")]
+    #[test_case("https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ"; "no query")]
+    #[test_case("https://example.com:8080/a?q=rust+lang&p=a%20b"; "encoded query")]
+    #[test_case("https://example.com/#/products?id=1"; "hash route")]
+    #[test_case("https://example.com/post#:~:text=utm_source"; "text fragment")]
+    fn clean_cow_borrowed(input: &str) {
+        let result = clean_cow(input).unwrap();
+
+        assert!(
+            matches!(result, Cow::Borrowed(r) if r == input),
+            "{result:?}"
+        );
+    }
+
+    #[test_case(
+        "https://example.com/my-post?utm_xyx=abc&id=12456",
+        "https://example.com/my-post?id=12456"; "tracker in query"
+    )]
+    #[test_case(
+        "https://twitter.com/elonmusk/status/1608273870901096454#ref_src=twsrc%5Etfw&from=home",
+        "https://twitter.com/elonmusk/status/1608273870901096454#from=home"; "tracker in fragment"
+    )]
+    #[test_case(
+        "https://www.youtube.com/redirect?event=channel_description&q=https%3A%2F%2Fwww.britishairways.com",
+        "https://www.britishairways.com/"; "handler"
+    )]
+    #[test_case(
+        "HTTPS://Example.com/?utm_source=x",
+        "https://example.com/"; "not normalized"
+    )]
+    #[test_case(
+        "https://example.com/?",
+        "https://example.com/"; "empty query"
+    )]
+    #[test_case(
+        "https://example.com/#/products?",
+        "https://example.com/#/products"; "empty hash route query"
+    )]
+    fn clean_cow_owned(input: &str, expected: &str) {
+        let result = clean_cow(input).unwrap();
+
+        assert!(matches!(result, Cow::Owned(_)), "{result:?}");
+        assert_eq!(result, expected);
+        assert_eq!(result, clean_str(input).unwrap());
+    }
+
+    #[test]
+    fn clean_cow_invalid() {
+        assert!(clean_cow("example.com/?utm_source=x").is_err());
+        assert!(clean_cow("https://exa mple.com/").is_err());
+        assert!(clean_cow("https://xn--/?a=1").is_err());
+        assert!(clean_cow("https://example.0x1f/").is_err());
+    }
+
+    #[test]
+    fn clean_batch_keeps_order() {
+        let inputs = (0..1_000)
+            .map(|i| match i % 3 {
+                0 => format!("https://example.com/{i}?utm_source=x&id={i}"),
+                1 => format!("example.com/{i}"),
+                _ => format!("https://example.com/{i}#fbclid=1"),
+            })
+            .collect::<Vec<_>>();
+
+        let results = clean_batch(&inputs);
+
+        assert_eq!(results.len(), inputs.len());
+        for (i, (input, result)) in inputs.iter().zip(results).enumerate() {
+            match i % 3 {
+                0 => assert_eq!(
+                    result.unwrap().to_string(),
+                    format!("https://example.com/{i}?id={i}")
+                ),
+                1 => assert_eq!(
+                    result.unwrap_err(),
+                    url::ParseError::RelativeUrlWithoutBase,
+                    "{input}"
+                ),
+                _ => assert_eq!(
+                    result.unwrap().to_string(),
+                    format!("https://example.com/{i}")
+                ),
+            }
+        }
+    }
+
     fn test_common(input: &str, expected: &str) {
         let result = clean(Url::parse(input).unwrap()).to_string();
 
@@ -404,5 +1624,21 @@ mod tests {
             M::StartsWith("utm_").matches_str(Some("utm_abc")),
             "starts_with"
         );
+        assert!(
+            M::Domain("x.com").matches_str(Some("x.com:443/a")),
+            "domain"
+        );
+        assert!(
+            M::Domain("x.com").matches_str(Some("mobile.x.com/a")),
+            "domain: subdomain"
+        );
+        assert!(
+            !M::Domain("x.com").matches_str(Some("box.com/x.com")),
+            "domain: suffix without a dot"
+        );
+        assert!(
+            M::Domain("x.com").matches_str(Some("mobile.x.com./a")),
+            "domain: fully qualified"
+        );
     }
 }
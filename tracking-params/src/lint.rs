@@ -0,0 +1,440 @@
+use std::fmt;
+
+use url::Url;
+
+use crate::{meta::key, Category, Rule, RuleSet, M};
+
+/// Needles of `Contains` param matchers shorter than this match too many keys.
+const MIN_CONTAINS_LENGTH: usize = 4;
+
+/// A problem found in a [`RuleSet`] by [`RuleSet::lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub kind: LintKind,
+    /// The [`crate::RuleMeta::id`] of the rule, or its hosts when it doesn't have one.
+    pub rule: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    /// A param or id listed twice.
+    Duplicate,
+    /// A param that a rule for all hosts of the same category already removes.
+    Shadowed,
+    /// A param that is both preserved and removed on the same hosts.
+    Contradiction,
+    /// A host matcher that never matches.
+    Unreachable,
+    /// A matcher that matches much more than it was probably meant to (eg. `Contains("track")`).
+    OverBroad,
+    /// A handler that none of the tested urls or examples runs, see [`RuleSet::lint_with_tests`].
+    UntestedHandler,
+    /// A rule without examples, see [`crate::Rule::with_examples`].
+    NoExamples,
+    /// A rule without sources, see [`crate::RuleMeta::sources`].
+    NoSources,
+}
+
+impl LintKind {
+    pub const ALL: [LintKind; 8] = [
+        LintKind::Duplicate,
+        LintKind::Shadowed,
+        LintKind::Contradiction,
+        LintKind::Unreachable,
+        LintKind::OverBroad,
+        LintKind::UntestedHandler,
+        LintKind::NoExamples,
+        LintKind::NoSources,
+    ];
+
+    /// `over_broad`, `no_examples`...
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintKind::Duplicate => "duplicate",
+            LintKind::Shadowed => "shadowed",
+            LintKind::Contradiction => "contradiction",
+            LintKind::Unreachable => "unreachable",
+            LintKind::OverBroad => "over_broad",
+            LintKind::UntestedHandler => "untested_handler",
+            LintKind::NoExamples => "no_examples",
+            LintKind::NoSources => "no_sources",
+        }
+    }
+
+    /// The kind with the given [`LintKind::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.name() == name)
+    }
+
+    /// Errors are always mistakes, warnings can be intended (eg. a short but specific param).
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            LintKind::Duplicate | LintKind::Contradiction | LintKind::Unreachable
+        )
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = if self.kind.is_error() {
+            "error"
+        } else {
+            "warning"
+        };
+        write!(f, "{level}[{}]: {}", self.rule, self.message)
+    }
+}
+
+pub(crate) fn lint(set: &RuleSet) -> Vec<Lint> {
+    let rules = set.rules();
+    let mut lints = vec![];
+    let mut push = |kind, rule: &Rule, message: String| {
+        if rule.allowed_lints.contains(&kind) {
+            return;
+        }
+        lints.push(Lint {
+            kind,
+            rule: key(rule),
+            message,
+        })
+    };
+
+    for (idx, rule) in rules.iter().enumerate() {
+        let earlier = &rules[..idx];
+
+        if !rule.meta.id.is_empty() && earlier.iter().any(|r| r.meta.id == rule.meta.id) {
+            push(LintKind::Duplicate, rule, "the id is already used".into());
+        }
+
+        let params = rule.params().collect::<Vec<_>>();
+        for (i, (param, category)) in params.iter().enumerate() {
+            let param = param.to_string();
+            let same_hosts = earlier
+                .iter()
+                .filter(|r| same_matchers(&r.host_path, &rule.host_path));
+            if params[..i].iter().any(|(p, _)| p.to_string() == param) {
+                push(
+                    LintKind::Duplicate,
+                    rule,
+                    format!("`{param}` is listed twice"),
+                );
+            } else if let Some(other) = same_hosts.into_iter().find(|r| {
+                r.params()
+                    .any(|(p, c)| c == *category && p.to_string() == param)
+            }) {
+                push(
+                    LintKind::Duplicate,
+                    rule,
+                    format!("`{param}` is also removed by `{}`", key(other)),
+                );
+            }
+        }
+
+        if !is_universal(rule) {
+            for other in rules.iter().filter(|r| is_universal(r)) {
+                for (param, category) in &params {
+                    if removes(other, param, Some(*category)) {
+                        push(
+                            LintKind::Shadowed,
+                            rule,
+                            format!("`{param}` is already removed by `{}`", key(other)),
+                        );
+                    }
+                }
+            }
+        }
+
+        let same_hosts = rules
+            .iter()
+            .filter(|r| is_universal(r) || same_matchers(&r.host_path, &rule.host_path));
+        for other in same_hosts {
+            for preserved in rule.preserve.iter().filter(|p| removes(other, p, None)) {
+                push(
+                    LintKind::Contradiction,
+                    rule,
+                    format!("`{preserved}` is preserved but removed by `{}`", key(other)),
+                );
+            }
+        }
+
+        if rule.host_path.is_empty() {
+            push(LintKind::Unreachable, rule, "no host matcher".into());
+        }
+        for host in &rule.host_path {
+            if let Some(reason) = unreachable(host) {
+                push(LintKind::Unreachable, rule, format!("`{host}` {reason}"));
+            }
+            if let Some(reason) = over_broad_host(host) {
+                push(LintKind::OverBroad, rule, format!("`{host}` {reason}"));
+            }
+        }
+        for (param, _) in &params {
+            if let Some(reason) = over_broad_param(param) {
+                push(LintKind::OverBroad, rule, format!("`{param}` {reason}"));
+            }
+        }
+
+        if rule.examples.is_empty() {
+            push(LintKind::NoExamples, rule, "no examples".into());
+        }
+        if rule.meta.sources.is_empty() {
+            push(LintKind::NoSources, rule, "no sources".into());
+        }
+    }
+
+    lints
+}
+
+pub(crate) fn untested_handlers(set: &RuleSet, tested_urls: &[&str]) -> Vec<Lint> {
+    let examples = set
+        .rules()
+        .iter()
+        .flat_map(|rule| rule.examples.iter().map(|(input, _)| input));
+    let tested = tested_urls
+        .iter()
+        .chain(examples)
+        .filter_map(|url| Url::parse(url).ok())
+        .collect::<Vec<_>>();
+
+    set.rules()
+        .iter()
+        .filter(|rule| rule.handler.is_some())
+        .filter(|rule| !rule.allowed_lints.contains(&LintKind::UntestedHandler))
+        .filter(|rule| {
+            !tested.iter().any(|url| {
+                set.matching(url)
+                    .rules()
+                    .any(|matched| std::ptr::eq(matched, *rule))
+            })
+        })
+        .map(|rule| Lint {
+            kind: LintKind::UntestedHandler,
+            rule: key(rule),
+            message: "none of the tested urls runs the handler".into(),
+        })
+        .collect()
+}
+
+fn is_universal(rule: &Rule) -> bool {
+    rule.host_path.iter().any(|m| matches!(m, M::Any))
+}
+
+fn same_matchers(a: &[M], b: &[M]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.to_string() == b.to_string())
+}
+
+/// Whether `rule` removes every key that `param` matches, as a param of `category` when set.
+fn removes(rule: &Rule, param: &M, category: Option<Category>) -> bool {
+    rule.params()
+        .filter(|(_, c)| category.is_none_or(|category| *c == category))
+        .any(|(p, _)| match (p, param) {
+            (M::Exact(a), M::Exact(b)) => a == b,
+            (M::StartsWith(a), M::Exact(b) | M::StartsWith(b)) => b.starts_with(a),
+            (M::Contains(a), M::Exact(b) | M::StartsWith(b) | M::Contains(b)) => b.contains(a),
+            (M::Any, _) => true,
+            _ => false,
+        })
+}
+
+fn unreachable(host: &M) -> Option<&'static str> {
+    match host {
+        M::Domain(d) if d.is_empty() || d.starts_with('.') || d.ends_with('.') => {
+            Some("is not a domain")
+        }
+        M::Domain(d) if d.contains(['/', ':']) => Some("is not a domain, use `Contains`"),
+        M::Domain(d) if d.chars().any(|c| c.is_ascii_uppercase()) => {
+            Some("has uppercase letters but hosts are lowercased")
+        }
+        M::Exact("") | M::StartsWith("") | M::Contains("") => Some("is empty"),
+        M::ContainsAll(all) if all.is_empty() || all.contains(&"") => Some("is empty"),
+        M::StartsWith(sw)
+            if sw
+                .split('/')
+                .next()
+                .is_some_and(|host| host.chars().any(|c| c.is_ascii_uppercase())) =>
+        {
+            Some("has uppercase letters but hosts are lowercased")
+        }
+        _ => None,
+    }
+}
+
+fn over_broad_host(host: &M) -> Option<&'static str> {
+    let needles = match host {
+        M::Contains(c) => vec![*c],
+        M::ContainsAll(all) => all.clone(),
+        _ => return None,
+    };
+    needles
+        .iter()
+        .all(|n| !n.contains(['.', '/']))
+        .then_some("matches any host or path containing it, use `Domain`")
+}
+
+fn over_broad_param(param: &M) -> Option<&'static str> {
+    match param {
+        M::Any => Some("removes every param"),
+        M::Contains(c) if c.len() < MIN_CONTAINS_LENGTH => {
+            Some("matches any key containing it, use `Exact` or `StartsWith`")
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RuleMeta;
+
+    fn rule(id: &'static str, host_path: Vec<M>, params: Vec<M>) -> Rule {
+        Rule::new(host_path, params)
+            .with_meta(RuleMeta {
+                id,
+                sources: &["https://example.com/params"],
+                ..Default::default()
+            })
+            .with_examples(&[("https://example.com/", "https://example.com/")])
+    }
+
+    fn kinds(rules: Vec<Rule>) -> Vec<(LintKind, String)> {
+        lint(&RuleSet::new(rules))
+            .into_iter()
+            .map(|l| (l.kind, l.rule))
+            .collect()
+    }
+
+    #[test]
+    fn finds_duplicates() {
+        assert_eq!(
+            kinds(vec![
+                rule(
+                    "a",
+                    vec![M::Any],
+                    vec![M::Exact("gclid"), M::Exact("gclid")]
+                ),
+                rule("b", vec![M::Domain("x.com")], vec![M::Exact("q")]),
+                rule("b", vec![M::Domain("x.com")], vec![M::Exact("q")]),
+            ]),
+            vec![
+                (LintKind::Duplicate, "a".into()),
+                (LintKind::Duplicate, "b".into()),
+                (LintKind::Duplicate, "b".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_shadowed_params_of_the_same_category() {
+        assert_eq!(
+            kinds(vec![
+                rule("universal", vec![M::Any], vec![M::StartsWith("utm_")]),
+                rule(
+                    "site",
+                    vec![M::Domain("x.com")],
+                    vec![M::Exact("utm_source")]
+                ),
+                rule(
+                    "other",
+                    vec![M::Domain("x.com")],
+                    vec![M::Exact("utm_source")]
+                )
+                .with_category(Category::SocialShare),
+            ]),
+            vec![(LintKind::Shadowed, "site".into())]
+        );
+    }
+
+    #[test]
+    fn finds_contradictions() {
+        assert_eq!(
+            kinds(vec![
+                rule("a", vec![M::Domain("x.com")], vec![M::Exact("t")])
+                    .preserving(vec![M::Exact("t")]),
+                rule("b", vec![M::Domain("y.com")], vec![M::Exact("v")]),
+                rule("c", vec![M::Domain("z.com")], vec![]).preserving(vec![M::Exact("v")]),
+            ]),
+            vec![(LintKind::Contradiction, "a".into())]
+        );
+    }
+
+    #[test]
+    fn finds_unreachable_and_over_broad_matchers() {
+        assert_eq!(
+            kinds(vec![
+                rule("a", vec![M::Domain("Example.com")], vec![]),
+                rule("b", vec![M::Domain("example.com/shop")], vec![]),
+                rule("c", vec![], vec![]),
+                rule("d", vec![M::Contains("track")], vec![M::Contains("kw")]),
+                rule("e", vec![M::ContainsAll(vec!["google.", "/url"])], vec![]),
+            ]),
+            vec![
+                (LintKind::Unreachable, "a".into()),
+                (LintKind::Unreachable, "b".into()),
+                (LintKind::Unreachable, "c".into()),
+                (LintKind::OverBroad, "d".into()),
+                (LintKind::OverBroad, "d".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_allowed_lints() {
+        assert_eq!(
+            kinds(vec![
+                rule("a", vec![M::Contains("track")], vec![M::Contains("kw")])
+                    .allowing_lint(LintKind::OverBroad),
+                rule("b", vec![M::Contains("track")], vec![])
+                    .with_examples(&[])
+                    .allowing_lint(LintKind::NoExamples),
+            ]),
+            vec![(LintKind::OverBroad, "b".into())]
+        );
+    }
+
+    #[test]
+    fn finds_rules_without_examples() {
+        assert_eq!(
+            kinds(vec![
+                rule("a", vec![M::Domain("x.com")], vec![]),
+                rule("b", vec![M::Domain("y.com")], vec![]).with_examples(&[]),
+            ]),
+            vec![(LintKind::NoExamples, "b".into())]
+        );
+    }
+
+    #[test]
+    fn finds_rules_without_sources() {
+        assert_eq!(
+            kinds(vec![
+                rule("a", vec![M::Domain("x.com")], vec![]),
+                rule("b", vec![M::Domain("y.com")], vec![]).with_meta(RuleMeta {
+                    id: "b",
+                    ..Default::default()
+                }),
+            ]),
+            vec![(LintKind::NoSources, "b".into())]
+        );
+    }
+
+    #[test]
+    fn builtin_rules_have_no_lints() {
+        assert_eq!(lint(RuleSet::builtin()), vec![]);
+    }
+
+    #[test]
+    fn finds_untested_handlers() {
+        let set = RuleSet::new(vec![
+            rule("tested", vec![M::Domain("x.com")], vec![]).with_handler(|url| url),
+            rule("untested", vec![M::Domain("y.com")], vec![]).with_handler(|url| url),
+            rule("example", vec![M::Domain("z.com")], vec![])
+                .with_examples(&[("https://z.com/", "https://z.com/")])
+                .with_handler(|url| url),
+        ]);
+        let lints = untested_handlers(&set, &["https://x.com/", "not an url"]);
+
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].rule, "untested");
+    }
+}
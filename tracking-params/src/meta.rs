@@ -0,0 +1,492 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{handler::Handler, Category, Decoding, HandlerSpec, Rule, RuleSet, M};
+
+/// Where a rule comes from and why it exists, see [`Rule::with_meta`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleMeta {
+    /// Unique and stable, eg. `amazon-tracking`.
+    pub id: &'static str,
+    pub description: &'static str,
+    /// Documentation of the params or the lists they were taken from.
+    pub sources: &'static [&'static str],
+    /// Version of this crate the rule was first released in.
+    pub added_in: &'static str,
+    /// Why the rule should no longer be used, it is still applied until it is removed.
+    pub deprecated: Option<&'static str>,
+}
+
+/// Identifies the rules a url was cleaned with, see [`crate::Cleaned::rule_set_version`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RuleSetVersion {
+    /// Set with [`RuleSet::with_version`], the version of this crate for [`RuleSet::builtin`].
+    pub version: &'static str,
+    /// Hash of what the rules do, it changes whenever a rule cleans differently but not
+    /// when only its metadata or examples change.
+    pub hash: u64,
+}
+
+impl fmt::Display for RuleSetVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}+{:016x}", self.version, self.hash)
+    }
+}
+
+/// Hash of what the rules do: their matchers, categories, flags and handlers, but not
+/// their metadata or examples.
+pub(crate) fn content_hash(rules: &[Rule]) -> u64 {
+    let mut hasher = Fnv::default();
+    for rule in rules {
+        hasher.matchers(&rule.host_path);
+        hasher.params(rule);
+        hasher.matchers(&rule.preserve);
+        hasher.category(rule.category);
+        hasher.bytes(&[rule.order_insensitive as u8]);
+        hasher.handler(rule.handler.as_ref());
+    }
+    hasher.0
+}
+
+/// FNV-1a, stable across builds and platforms unlike `DefaultHasher`. Every value is
+/// written with a tag or its length so that different rules can't write the same bytes.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv {
+    fn bytes(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn str(&mut self, s: &str) {
+        self.bytes(&(s.len() as u64).to_le_bytes());
+        self.bytes(s.as_bytes());
+    }
+
+    fn strs(&mut self, strs: &[&str]) {
+        self.bytes(&(strs.len() as u64).to_le_bytes());
+        for s in strs {
+            self.str(s);
+        }
+    }
+
+    fn category(&mut self, category: Category) {
+        self.str(category.name());
+    }
+
+    fn matcher(&mut self, matcher: &M) {
+        match matcher {
+            M::Any => self.bytes(&[0]),
+            M::AllBut(s) => {
+                self.bytes(&[1]);
+                self.str(s);
+            }
+            M::ContainsAll(all) => {
+                self.bytes(&[2]);
+                self.strs(all);
+            }
+            M::Exact(s) => {
+                self.bytes(&[3]);
+                self.str(s);
+            }
+            M::StartsWith(s) => {
+                self.bytes(&[4]);
+                self.str(s);
+            }
+            M::Contains(s) => {
+                self.bytes(&[5]);
+                self.str(s);
+            }
+            M::Domain(s) => {
+                self.bytes(&[6]);
+                self.str(s);
+            }
+        }
+    }
+
+    fn matchers(&mut self, matchers: &[M]) {
+        self.bytes(&(matchers.len() as u64).to_le_bytes());
+        for matcher in matchers {
+            self.matcher(matcher);
+        }
+    }
+
+    fn params(&mut self, rule: &Rule) {
+        self.bytes(&(rule.params.len() as u64).to_le_bytes());
+        for (param, category) in rule.params() {
+            self.matcher(param);
+            self.category(category);
+        }
+    }
+
+    /// Closures can't be hashed, only their presence is.
+    fn handler(&mut self, handler: Option<&Handler>) {
+        match handler {
+            None => self.bytes(&[0]),
+            Some(Handler::Spec(spec)) => {
+                self.bytes(&[1]);
+                self.spec(spec);
+            }
+            Some(Handler::Custom(_)) => self.bytes(&[2]),
+        }
+    }
+
+    fn spec(&mut self, spec: &HandlerSpec) {
+        match spec {
+            HandlerSpec::ExtractFromQuery {
+                keys,
+                path_contains,
+                decode,
+            } => {
+                self.bytes(&[0]);
+                self.strs(keys);
+                self.strs(path_contains);
+                self.decodings(decode);
+            }
+            HandlerSpec::ExtractFromPathSuffix { after, decode } => {
+                self.bytes(&[1]);
+                self.str(after);
+                self.decodings(decode);
+            }
+            HandlerSpec::RewriteHost { from, to } => {
+                self.bytes(&[2]);
+                self.str(from);
+                self.str(to);
+            }
+            HandlerSpec::StripPath(matcher) => {
+                self.bytes(&[3]);
+                self.matcher(matcher);
+            }
+            HandlerSpec::Chain(specs) => {
+                self.bytes(&[4]);
+                self.bytes(&(specs.len() as u64).to_le_bytes());
+                for spec in specs {
+                    self.spec(spec);
+                }
+            }
+        }
+    }
+
+    fn decodings(&mut self, decodings: &[Decoding]) {
+        self.bytes(&(decodings.len() as u64).to_le_bytes());
+        for decoding in decodings {
+            match decoding {
+                Decoding::Percent => self.bytes(&[0]),
+                Decoding::Base64 => self.bytes(&[1]),
+                Decoding::Jwt(claim) => {
+                    self.bytes(&[2]);
+                    self.str(claim);
+                }
+            }
+        }
+    }
+}
+
+/// A difference between two [`RuleSet`]s, see [`RuleSet::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleChange {
+    Added {
+        id: String,
+        description: &'static str,
+    },
+    Removed {
+        id: String,
+    },
+    Deprecated {
+        id: String,
+        reason: &'static str,
+    },
+    /// The rule changed, `added` and `removed` are the params it now removes or no longer
+    /// removes and are empty when something else changed (eg. the hosts it applies to).
+    Changed {
+        id: String,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+}
+
+/// The changes between two [`RuleSet`]s, its `Display` is a changelog:
+///
+/// ```text
+/// Rules 0.1.6+3f1d6a8f4b2c9e7d -> 0.2.0+5a0b1c2d3e4f5a6b
+/// * Added `amazon-redirect`: Extracts the destination of Amazon's internal redirector
+/// * Changed `youtube-share`: removes `pp`, no longer removes `kw*`
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSetDiff {
+    pub from: RuleSetVersion,
+    pub to: RuleSetVersion,
+    pub changes: Vec<RuleChange>,
+}
+
+impl fmt::Display for RuleSetDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Rules {} -> {}", self.from, self.to)?;
+        if self.changes.is_empty() {
+            return writeln!(f, "* No changes");
+        }
+
+        let params = |params: &[String]| {
+            params
+                .iter()
+                .map(|p| format!("`{p}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        for change in &self.changes {
+            match change {
+                RuleChange::Added { id, description } => {
+                    writeln!(f, "* Added `{id}`: {description}")?
+                }
+                RuleChange::Removed { id } => writeln!(f, "* Removed `{id}`")?,
+                RuleChange::Deprecated { id, reason } => {
+                    writeln!(f, "* Deprecated `{id}`: {reason}")?
+                }
+                RuleChange::Changed { id, added, removed } => {
+                    let mut details = vec![];
+                    if !added.is_empty() {
+                        details.push(format!("removes {}", params(added)));
+                    }
+                    if !removed.is_empty() {
+                        details.push(format!("no longer removes {}", params(removed)));
+                    }
+                    match details.is_empty() {
+                        true => writeln!(f, "* Changed `{id}`")?,
+                        false => writeln!(f, "* Changed `{id}`: {}", details.join(", "))?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Names a rule by its [`RuleMeta::id`], or by its hosts when it doesn't have one.
+pub(crate) fn key(rule: &Rule) -> String {
+    match rule.meta.id {
+        "" => rule
+            .host_path
+            .iter()
+            .map(M::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        id => id.to_string(),
+    }
+}
+
+/// The [`key`] of each rule, made unique by appending `#2`, `#3`... to the rules that
+/// share it (eg. two rules without id on the same hosts).
+fn unique_keys(rules: &[Rule]) -> Vec<String> {
+    let mut seen = HashMap::<String, usize>::new();
+    rules
+        .iter()
+        .map(|rule| {
+            let key = key(rule);
+            let count = seen.entry(key.clone()).or_default();
+            *count += 1;
+            match *count {
+                1 => key,
+                n => format!("{key} #{n}"),
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn diff(old: &RuleSet, new: &RuleSet) -> RuleSetDiff {
+    let old_keys = unique_keys(old.rules());
+    let old_rules = old_keys.iter().zip(old.rules()).collect::<HashMap<_, _>>();
+    let new_keys = unique_keys(new.rules());
+    let mut changes = vec![];
+
+    for (id, rule) in new_keys.iter().zip(new.rules()) {
+        let Some(previous) = old_rules.get(id) else {
+            changes.push(RuleChange::Added {
+                id: id.clone(),
+                description: rule.meta.description,
+            });
+            continue;
+        };
+
+        if let (None, Some(reason)) = (previous.meta.deprecated, rule.meta.deprecated) {
+            changes.push(RuleChange::Deprecated {
+                id: id.clone(),
+                reason,
+            });
+        }
+
+        let params = |rule: &Rule| {
+            rule.params()
+                .map(|(p, _)| p.to_string())
+                .collect::<Vec<_>>()
+        };
+        let (before, after) = (params(previous), params(rule));
+        let added = after
+            .iter()
+            .filter(|p| !before.contains(p))
+            .cloned()
+            .collect::<Vec<_>>();
+        let removed = before
+            .iter()
+            .filter(|p| !after.contains(p))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // Everything but the params and the metadata.
+        let shape = |rule: &Rule| {
+            let mut hasher = Fnv::default();
+            hasher.matchers(&rule.host_path);
+            hasher.matchers(&rule.preserve);
+            for (_, category) in rule.params() {
+                hasher.category(category);
+            }
+            hasher.bytes(&[rule.order_insensitive as u8]);
+            hasher.handler(rule.handler.as_ref());
+            hasher.0
+        };
+        let other_changes = shape(previous) != shape(rule);
+        if !added.is_empty() || !removed.is_empty() || other_changes {
+            changes.push(RuleChange::Changed {
+                id: id.clone(),
+                added,
+                removed,
+            });
+        }
+    }
+
+    changes.extend(
+        old_keys
+            .into_iter()
+            .filter(|id| !new_keys.contains(id))
+            .map(|id| RuleChange::Removed { id }),
+    );
+
+    RuleSetDiff {
+        from: old.version(),
+        to: new.version(),
+        changes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &'static str, params: Vec<M>) -> Rule {
+        Rule::new(vec![M::Domain("example.com")], params).with_meta(RuleMeta {
+            id,
+            description: "Example",
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn hash_follows_the_content() {
+        let a = RuleSet::new(vec![rule("a", vec![M::Exact("x")])]);
+        let same = RuleSet::new(vec![rule("a", vec![M::Exact("x")])]).with_version("1.0.0");
+        let other = RuleSet::new(vec![rule("a", vec![M::Exact("y")])]);
+
+        let mut described = rule("a", vec![M::Exact("x")]);
+        described.meta.description = "Another description";
+        let described = RuleSet::new(vec![described]);
+        let categorized = RuleSet::new(vec![
+            rule("a", vec![]).params_in(Category::Affiliate, vec![M::Exact("x")])
+        ]);
+
+        assert_eq!(a.version().hash, same.version().hash);
+        assert_eq!(a.version().hash, described.version().hash, "metadata");
+        assert_ne!(a.version().hash, other.version().hash);
+        assert_ne!(a.version().hash, categorized.version().hash, "category");
+        assert_eq!(
+            same.version().to_string(),
+            format!("1.0.0+{:016x}", a.version().hash)
+        );
+    }
+
+    #[test]
+    fn diffs_rule_sets() {
+        let old = RuleSet::new(vec![
+            rule("kept", vec![M::Exact("x")]),
+            rule("changed", vec![M::Exact("a"), M::StartsWith("b_")]),
+            rule("recategorized", vec![M::Exact("x")]),
+            rule("removed", vec![M::Exact("x")]),
+        ])
+        .with_version("1.0.0");
+        let mut deprecated = rule("kept", vec![M::Exact("x")]);
+        deprecated.meta.deprecated = Some("The site is gone");
+        let new = RuleSet::new(vec![
+            deprecated,
+            rule("changed", vec![M::Exact("a"), M::Contains("clid")]),
+            rule("recategorized", vec![M::Exact("x")]).with_category(Category::ClickId),
+            rule("added", vec![M::Exact("x")]),
+        ])
+        .with_version("1.1.0");
+
+        let diff = old.diff(&new);
+
+        assert_eq!(
+            diff.changes,
+            vec![
+                RuleChange::Deprecated {
+                    id: "kept".to_string(),
+                    reason: "The site is gone"
+                },
+                RuleChange::Changed {
+                    id: "changed".to_string(),
+                    added: vec!["*clid*".to_string()],
+                    removed: vec!["b_*".to_string()]
+                },
+                RuleChange::Changed {
+                    id: "recategorized".to_string(),
+                    added: vec![],
+                    removed: vec![]
+                },
+                RuleChange::Added {
+                    id: "added".to_string(),
+                    description: "Example"
+                },
+                RuleChange::Removed {
+                    id: "removed".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            diff.to_string().lines().skip(1).collect::<Vec<_>>(),
+            vec![
+                "* Deprecated `kept`: The site is gone",
+                "* Changed `changed`: removes `*clid*`, no longer removes `b_*`",
+                "* Changed `recategorized`",
+                "* Added `added`: Example",
+                "* Removed `removed`",
+            ]
+        );
+        assert_eq!(old.diff(&old).changes, vec![]);
+    }
+
+    #[test]
+    fn diffs_rules_without_id() {
+        let unnamed = |params| Rule::new(vec![M::Domain("example.com")], params);
+        let old = RuleSet::new(vec![
+            unnamed(vec![M::Exact("a")]),
+            unnamed(vec![M::Exact("b")]),
+        ]);
+        let new = RuleSet::new(vec![
+            unnamed(vec![M::Exact("a")]),
+            unnamed(vec![M::Exact("c")]),
+        ]);
+
+        assert_eq!(
+            old.diff(&new).changes,
+            vec![RuleChange::Changed {
+                id: "example.com #2".to_string(),
+                added: vec!["c".to_string()],
+                removed: vec!["b".to_string()]
+            }]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::borrow::Cow;
+
+use url::Url;
+
+use crate::{Fragment, Options, RuleSet};
+
+/// Cleans the `http(s)` urls found in the param values of the query and the fragment
+/// of `url`, see [`Options::nested_urls_depth`].
+///
+/// The outer url is kept, only the nested urls are rewritten and they are escaped
+/// the same way they were.
+pub(crate) fn clean_nested_urls(mut url: Url, set: &RuleSet, options: &Options) -> Url {
+    let options = Options {
+        nested_urls_depth: options.nested_urls_depth.saturating_sub(1),
+        ..options.clone()
+    };
+    let clean_value = |value: &str| clean_value(value, set, &options);
+
+    if let Some(query) = url.query() {
+        if let Cow::Owned(query) = map_values(query, clean_value) {
+            url.set_query(Some(&query));
+        }
+    }
+
+    if let Some(fragment) = url.fragment() {
+        let cleaned = match Fragment::parse(fragment) {
+            Fragment::Params(params) => map_values(params, clean_value),
+            Fragment::Route(route) => match route.split_once('?') {
+                Some((path, query)) => match map_values(query, clean_value) {
+                    Cow::Owned(query) => Cow::Owned(format!("{path}?{query}")),
+                    Cow::Borrowed(_) => Cow::Borrowed(route),
+                },
+                None => Cow::Borrowed(route),
+            },
+            Fragment::TextDirective(_) | Fragment::Anchor(_) => Cow::Borrowed(fragment),
+        };
+        if let Cow::Owned(fragment) = cleaned {
+            url.set_fragment(Some(&fragment));
+        }
+    }
+
+    url
+}
+
+/// Applies `f` to the raw values of `key=value` pairs, borrowed when none of them changed.
+fn map_values<'a>(pairs: &'a str, f: impl Fn(&str) -> Option<String>) -> Cow<'a, str> {
+    let mut changed = false;
+    let mapped = pairs
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => match f(value) {
+                Some(value) => {
+                    changed = true;
+                    Cow::Owned(format!("{key}={value}"))
+                }
+                None => Cow::Borrowed(pair),
+            },
+            None => Cow::Borrowed(pair),
+        })
+        .collect::<Vec<_>>();
+
+    if changed {
+        Cow::Owned(mapped.join("&"))
+    } else {
+        Cow::Borrowed(pairs)
+    }
+}
+
+/// The cleaned version of the raw param `value` when it's an url that has something to clean.
+pub(crate) fn clean_value(value: &str, set: &RuleSet, options: &Options) -> Option<String> {
+    // `url=https://site/?id=1` was not escaped at all, `url=https%3A%2F%2Fsite%2F%3Fid%3D1` was.
+    let escaped = !value.contains("://");
+    let nested = if escaped {
+        urlencoding::decode(value).ok()?
+    } else {
+        Cow::Borrowed(value)
+    };
+    if !nested.starts_with("http://") && !nested.starts_with("https://") {
+        return None;
+    }
+
+    let cleaned = set
+        .clean_with(Url::parse(&nested).ok()?, options)
+        .to_string();
+    if cleaned == nested {
+        return None;
+    }
+
+    if !escaped && !cleaned.contains(['&', '#']) {
+        Some(cleaned)
+    } else {
+        Some(escape_like(&cleaned, value))
+    }
+}
+
+/// Percent-encodes `input` the way `original` was: characters that `original` has
+/// unescaped are kept as is and hex digits use the same case.
+pub(crate) fn escape_like(input: &str, original: &str) -> String {
+    let lowercase = original
+        .split('%')
+        .skip(1)
+        .any(|escape| escape.bytes().take(2).any(|b| b.is_ascii_lowercase()));
+
+    let mut escaped = String::with_capacity(input.len() * 3 / 2);
+    for b in input.bytes() {
+        let unreserved = b.is_ascii_alphanumeric() || b"-._~".contains(&b);
+        let kept = b != b'%' && b != b'&' && b != b'#' && original.as_bytes().contains(&b);
+        if unreserved || kept {
+            escaped.push(b as char);
+        } else if lowercase {
+            escaped.push_str(&format!("%{b:02x}"));
+        } else {
+            escaped.push_str(&format!("%{b:02X}"));
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_like_the_original() {
+        assert_eq!(
+            escape_like(
+                "https://site/?id=1",
+                "https%3A%2F%2Fsite%2F%3Fid%3D1%26utm_source%3Dx"
+            ),
+            "https%3A%2F%2Fsite%2F%3Fid%3D1"
+        );
+        assert_eq!(
+            escape_like(
+                "https://site/?id=1",
+                "https%3a%2f%2fsite%2f%3fid%3d1%26utm_source%3dx"
+            ),
+            "https%3a%2f%2fsite%2f%3fid%3d1"
+        );
+        assert_eq!(
+            escape_like(
+                "https://site/a?id=1",
+                "https:%2F%2Fsite%2Fa?id%3D1%26utm_source%3Dx"
+            ),
+            "https:%2F%2Fsite%2Fa?id%3D1"
+        );
+        assert_eq!(
+            escape_like(
+                "https://site/?q=%5E",
+                "https%3A%2F%2Fsite%2F%3Fq%3D%255E%26fbclid%3D1"
+            ),
+            "https%3A%2F%2Fsite%2F%3Fq%3D%255E"
+        );
+    }
+}
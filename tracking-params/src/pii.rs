@@ -0,0 +1,280 @@
+//! Detection of personal data in param values, see [`crate::Options::pii`].
+use std::borrow::Cow;
+
+use base64::{
+    engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use url::Url;
+
+use crate::{query_key, Fragment};
+
+/// The value that replaces personal data with [`PiiPolicy::Redact`].
+pub const REDACTED: &str = "REDACTED";
+
+/// Keys whose hex value is a hash of an email address (eg. `email_sha256`, `hem`).
+const HASHED_EMAIL_KEYS: &[&str] = &["em", "hem", "ehash", "uhash"];
+
+/// Keys whose value is a phone number even without the `+` of the international format,
+/// lowercased and without separators (eg. `phone_number` or `mobileNo`). Whole keys only,
+/// `telemetry`, `tel_aviv` or `cellid` are not phone keys.
+const PHONE_KEYS: &[&str] = &[
+    "cell",
+    "cellnumber",
+    "cellphone",
+    "mobile",
+    "mobileno",
+    "mobilenumber",
+    "mobilephone",
+    "msisdn",
+    "phone",
+    "phoneno",
+    "phonenumber",
+    "tel",
+    "telephone",
+    "telephonenumber",
+    "telno",
+];
+
+/// What to do with the params that contain personal data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiPolicy {
+    /// Remove the whole `key=value` pair.
+    Remove,
+    /// Keep the key and replace the value with [`REDACTED`].
+    Redact,
+}
+
+/// The kind of personal data found in a param.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiCategory {
+    /// `jane@corp.com`, as is, percent-encoded or base64-encoded.
+    Email,
+    /// `+14155552671`, or digits in a param named like a phone number.
+    Phone,
+    /// The MD5, SHA-1 or SHA-256 hex digest of an email address, found by its key.
+    HashedEmail,
+}
+
+/// A param with personal data, see [`crate::Cleaned::pii_findings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiFinding {
+    /// The decoded key, eg. `email`.
+    pub key: String,
+    pub category: PiiCategory,
+    /// Whether the param was in the fragment instead of the query.
+    pub in_fragment: bool,
+}
+
+/// Applies `policy` to the params of the query and fragment of `url` that contain personal data.
+pub(crate) fn scrub(mut url: Url, policy: PiiPolicy) -> (Url, Vec<PiiFinding>) {
+    let mut findings = vec![];
+
+    if let Some(query) = url.query() {
+        if let Cow::Owned(query) = scrub_pairs(query, policy, false, &mut findings) {
+            url.set_query((!query.is_empty()).then_some(query.as_str()));
+        }
+    }
+
+    if let Some(fragment) = url.fragment() {
+        let scrubbed = match Fragment::parse(fragment) {
+            Fragment::Params(params) => scrub_pairs(params, policy, true, &mut findings),
+            Fragment::Route(route) => match route.split_once('?') {
+                Some((path, query)) => match scrub_pairs(query, policy, true, &mut findings) {
+                    Cow::Owned(query) if query.is_empty() => Cow::Owned(path.to_string()),
+                    Cow::Owned(query) => Cow::Owned(format!("{path}?{query}")),
+                    Cow::Borrowed(_) => Cow::Borrowed(route),
+                },
+                None => Cow::Borrowed(route),
+            },
+            Fragment::TextDirective(_) | Fragment::Anchor(_) => Cow::Borrowed(fragment),
+        };
+        if let Cow::Owned(fragment) = scrubbed {
+            url.set_fragment((!fragment.is_empty()).then_some(fragment.as_str()));
+        }
+    }
+
+    (url, findings)
+}
+
+/// Applies `policy` to the `key=value` pairs with personal data, borrowed when there are none.
+fn scrub_pairs<'a>(
+    pairs: &'a str,
+    policy: PiiPolicy,
+    in_fragment: bool,
+    findings: &mut Vec<PiiFinding>,
+) -> Cow<'a, str> {
+    let found = findings.len();
+    let scrubbed = pairs
+        .split('&')
+        .filter_map(|pair| {
+            let Some((raw_key, value)) = pair.split_once('=') else {
+                return Some(Cow::Borrowed(pair));
+            };
+            let key = query_key(pair);
+            let Some(category) = detect(&key, value) else {
+                return Some(Cow::Borrowed(pair));
+            };
+
+            findings.push(PiiFinding {
+                key: key.into_owned(),
+                category,
+                in_fragment,
+            });
+            match policy {
+                PiiPolicy::Remove => None,
+                PiiPolicy::Redact => Some(Cow::Owned(format!("{raw_key}={REDACTED}"))),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if findings.len() == found {
+        Cow::Borrowed(pairs)
+    } else {
+        Cow::Owned(scrubbed.join("&"))
+    }
+}
+
+/// The kind of personal data in the raw param `value`, if any.
+pub(crate) fn detect(key: &str, value: &str) -> Option<PiiCategory> {
+    // `+` is not decoded to a space, it starts phone numbers and is common in email addresses.
+    let value = urlencoding::decode(value).ok()?;
+    let value = value.trim();
+    let key = key.to_ascii_lowercase();
+
+    if is_email(value) || decode_base64(value).is_some_and(|decoded| is_email(&decoded)) {
+        return Some(PiiCategory::Email);
+    }
+
+    let digest_length = matches!(value.len(), 32 | 40 | 64);
+    if digest_length
+        && value.bytes().all(|b| b.is_ascii_hexdigit())
+        && (key.contains("mail") || HASHED_EMAIL_KEYS.contains(&&*key))
+    {
+        return Some(PiiCategory::HashedEmail);
+    }
+
+    let phone_key = PHONE_KEYS.contains(&&*key.replace(|c: char| !c.is_ascii_alphanumeric(), ""));
+    if is_phone(value, phone_key) {
+        return Some(PiiCategory::Phone);
+    }
+
+    None
+}
+
+/// `jane.doe+news@corp.example`
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    let Some((_, tld)) = domain.rsplit_once('.') else {
+        return false;
+    };
+
+    !local.is_empty()
+        && local
+            .chars()
+            .all(|c| c.is_alphanumeric() || ".!#$%&'*+/=?^_`{|}~-".contains(c))
+        && domain.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+        && tld.len() >= 2
+        && tld.chars().all(char::is_alphabetic)
+}
+
+/// `+14155552671` in the E.164 format, separators are allowed (eg. `+1 (415) 555-2671`).
+/// When `phone_key` is set the `+` is optional.
+fn is_phone(value: &str, phone_key: bool) -> bool {
+    let digits = match value.strip_prefix('+') {
+        Some(digits) => digits,
+        None if phone_key => value,
+        None => return false,
+    };
+    if !digits
+        .chars()
+        .all(|c| c.is_ascii_digit() || " -.()".contains(c))
+    {
+        return false;
+    }
+
+    let digits = digits
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect::<String>();
+    let min_length = if value.starts_with('+') { 8 } else { 7 };
+    (min_length..=15).contains(&digits.len()) && !digits.starts_with('0')
+}
+
+/// `value` decoded from standard or url-safe base64, when it is valid utf-8.
+pub(crate) fn decode_base64(value: &str) -> Option<String> {
+    let value = value.trim_end_matches('=');
+    if value.len() < 8 {
+        return None;
+    }
+
+    let bytes = if value.contains(['-', '_']) {
+        URL_SAFE_NO_PAD.decode(value)
+    } else {
+        STANDARD_NO_PAD.decode(value)
+    };
+    String::from_utf8(bytes.ok()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_emails() {
+        assert!(is_email("jane.doe+news@corp.example"));
+        assert!(!is_email("@corp.com"));
+        assert!(!is_email("jane@localhost"));
+        assert!(!is_email("jane@corp.c0m"));
+        assert!(!is_email("user@host name.com"));
+    }
+
+    #[test]
+    fn detects_phones() {
+        assert!(is_phone("+14155552671", false));
+        assert!(is_phone("+1 (415) 555-2671", false));
+        assert!(is_phone("4155552671", true));
+        assert!(!is_phone("4155552671", false));
+        assert!(!is_phone("+123", false));
+        assert!(!is_phone("+1415555267100000", false));
+        assert!(!is_phone("+1-415-CALL-NOW", false));
+    }
+
+    #[test]
+    fn detects_categories() {
+        assert_eq!(detect("e", "jane%40corp.com"), Some(PiiCategory::Email));
+        assert_eq!(
+            detect("u", "amFuZUBjb3JwLmNvbQ=="),
+            Some(PiiCategory::Email)
+        );
+        assert_eq!(
+            detect(
+                "email_sha256",
+                "3f1d6a8f4b2c9e7d5a0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f"
+            ),
+            Some(PiiCategory::HashedEmail)
+        );
+        assert_eq!(
+            detect("commit", "3f1d6a8f4b2c9e7d5a0b1c2d3e4f5a6b7c8d9e0f"),
+            None
+        );
+        assert_eq!(detect("to", "+14155552671"), Some(PiiCategory::Phone));
+        assert_eq!(detect("to", "%2B14155552671"), Some(PiiCategory::Phone));
+        assert_eq!(
+            detect("phone_number", "4155552671"),
+            Some(PiiCategory::Phone)
+        );
+        assert_eq!(detect("mobileNo", "4155552671"), Some(PiiCategory::Phone));
+        assert_eq!(detect("tel", "4155552671"), Some(PiiCategory::Phone));
+        assert_eq!(detect("hotel", "4155552671"), None);
+        for key in ["telemetry", "television", "tel_aviv", "cellid", "mobileapp"] {
+            assert_eq!(detect(key, "4155552671"), None, "{key}");
+        }
+        assert_eq!(detect("id", "14155552671"), None);
+        assert_eq!(detect("q", "rust"), None);
+    }
+}
@@ -0,0 +1,202 @@
+//! Property tests of the guarantees of cleaning (see the crate documentation), on urls
+//! built from the host and param patterns of the builtin rules.
+use proptest::{prelude::*, sample::select};
+use url::Url;
+
+use crate::{
+    clean_cow, clean_str, Canonicalize, HandlerSpec, Options, PiiPolicy, Rule, RuleSet, M,
+};
+
+/// A `host/path` matched by `m`.
+fn host_path(m: &M) -> String {
+    let host = |needle: &str| match needle {
+        n if n.ends_with('.') => format!("www.{n}com"),
+        n if n.contains('.') => n.to_string(),
+        n => format!("www.{n}.com"),
+    };
+    match m {
+        M::Any | M::AllBut(_) => "example.com/".to_string(),
+        M::Exact(e) | M::StartsWith(e) => e.to_string(),
+        M::Domain(d) => format!("www.{d}/"),
+        M::Contains(c) if c.starts_with('/') => format!("shop.example.com{c}"),
+        M::Contains(c) => format!("{}/", host(c)),
+        M::ContainsAll(all) => {
+            let (hosts, paths): (Vec<&str>, Vec<&str>) =
+                all.iter().partition(|n| !n.starts_with('/'));
+            let mut host_path = hosts.first().map_or("example.com".to_string(), |h| host(h));
+            for needle in hosts.iter().skip(1).chain(&paths) {
+                host_path.push('/');
+                host_path.push_str(needle.trim_start_matches('/'));
+            }
+            host_path
+        }
+    }
+}
+
+/// A key matched by `m`.
+fn key(m: &M) -> String {
+    match m {
+        M::Any | M::AllBut(_) => "any_key".to_string(),
+        M::Exact(e) | M::Domain(e) => e.to_string(),
+        M::StartsWith(sw) => format!("{sw}x"),
+        M::Contains(c) => format!("x{c}x"),
+        M::ContainsAll(all) => all.concat(),
+    }
+}
+
+fn builtin_rules() -> Vec<&'static Rule> {
+    RuleSet::builtin().rules().iter().collect()
+}
+
+fn value() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        Just("=".to_string()),
+        "=[a-zA-Z0-9_.~-]{1,12}",
+        "=[a-z]{1,4}(%20|%2F|%3D|%26|\\+)[a-z]{1,4}",
+    ]
+}
+
+/// `key=value` pairs, with keys of `rule` and of other sites.
+fn pairs(rule: &'static Rule) -> impl Strategy<Value = String> {
+    let keys = rule
+        .params()
+        .map(|(param, _)| param)
+        .chain(&rule.preserve)
+        .map(key)
+        .chain(["id", "q", "page", "v", "utm_source", "fbclid", ""].map(String::from))
+        .collect::<Vec<_>>();
+
+    prop::collection::vec((select(keys), value()), 0..6).prop_map(|pairs| {
+        pairs
+            .into_iter()
+            .map(|(key, value)| format!("{key}{value}"))
+            .collect::<Vec<_>>()
+            .join("&")
+    })
+}
+
+fn fragment(rule: &'static Rule) -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        Just("#comments".to_string()),
+        pairs(rule).prop_map(|pairs| format!("#{pairs}")),
+        pairs(rule).prop_map(|pairs| format!("#/route?{pairs}")),
+    ]
+}
+
+/// An `http(s)` url some of the builtin rules apply to.
+fn site_url() -> impl Strategy<Value = String> {
+    select(builtin_rules())
+        .prop_flat_map(|rule| {
+            (
+                select(vec!["http", "https"]),
+                select(rule.host_path.clone()),
+                pairs(rule),
+                fragment(rule),
+            )
+        })
+        .prop_map(|(scheme, host, query, fragment)| {
+            format!("{scheme}://{}?{query}{fragment}", host_path(&host))
+        })
+        .prop_filter("must be a valid url", |url| Url::parse(url).is_ok())
+}
+
+/// A url that builtin redirectors extract `site_url` from, through up to 8 redirectors.
+fn redirect_url() -> impl Strategy<Value = String> {
+    let redirectors = builtin_rules()
+        .into_iter()
+        .filter(|rule| {
+            matches!(
+                rule.handler_spec(),
+                Some(HandlerSpec::ExtractFromQuery { .. })
+            )
+        })
+        .collect::<Vec<_>>();
+
+    site_url().prop_recursive(8, 8, 1, move |destination| {
+        (select(redirectors.clone()), destination).prop_map(|(rule, destination)| {
+            let Some(HandlerSpec::ExtractFromQuery {
+                keys,
+                path_contains,
+                ..
+            }) = rule.handler_spec()
+            else {
+                unreachable!()
+            };
+            let mut host_path = host_path(&rule.host_path[0]);
+            for path in path_contains {
+                host_path = format!("{}/{}", host_path.trim_end_matches('/'), path);
+            }
+            format!(
+                "https://{host_path}?{}={}",
+                keys[0],
+                urlencoding::encode(&destination)
+            )
+        })
+    })
+}
+
+fn any_url() -> impl Strategy<Value = String> {
+    prop_oneof![3 => site_url(), 1 => redirect_url()]
+}
+
+/// An `https` url whose host `url` may reject or rewrite (punycode, IPv4 looking labels...).
+fn odd_host_url() -> impl Strategy<Value = String> {
+    "https://(xn--)?[a-z0-9-]{0,6}(\\.(xn--|0x)?[a-z0-9-]{0,4}){0,2}(:[0-9]{1,5})?/(\\?[a-z_]{1,4}=1)?"
+}
+
+fn all_options() -> Options {
+    Options {
+        expand_short_urls: true,
+        nested_urls_depth: 2,
+        canonicalize: Canonicalize::all(),
+        aggressive: true,
+        pii: Some(PiiPolicy::Redact),
+        ..Default::default()
+    }
+}
+
+/// Whether the `&` and `?` separated parts of `cleaned` are parts of `input`, in the same order.
+fn only_removes(input: Option<&str>, cleaned: Option<&str>) -> bool {
+    let parts = |s: Option<&str>| {
+        s.unwrap_or_default()
+            .split(['&', '?'])
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    let mut input = parts(input).into_iter();
+    parts(cleaned).iter().all(|part| input.any(|p| &p == part))
+}
+
+proptest! {
+    #[test]
+    fn cleaning_is_idempotent(url in any_url()) {
+        let once = clean_str(&url).unwrap();
+        prop_assert_eq!(clean_str(&once).unwrap(), once);
+    }
+
+    #[test]
+    fn cleaning_with_all_options_is_idempotent(url in any_url()) {
+        let options = all_options();
+        let once = crate::clean_with(Url::parse(&url).unwrap(), &options).to_string();
+        let twice = crate::clean_with(Url::parse(&once).unwrap(), &options).to_string();
+        prop_assert_eq!(twice, once);
+    }
+
+    #[test]
+    fn cleaning_only_removes(url in site_url()) {
+        let input = Url::parse(&url).unwrap();
+        let cleaned = crate::clean(input.clone());
+
+        prop_assert_eq!(&input[..url::Position::AfterPath], &cleaned[..url::Position::AfterPath]);
+        prop_assert!(only_removes(input.query(), cleaned.query()), "query of {}", cleaned);
+        prop_assert!(only_removes(input.fragment(), cleaned.fragment()), "fragment of {}", cleaned);
+    }
+
+    #[test]
+    fn clean_cow_is_clean_str(url in prop_oneof![any_url(), odd_host_url()]) {
+        prop_assert_eq!(clean_cow(&url).map(|url| url.into_owned()), clean_str(&url));
+    }
+}
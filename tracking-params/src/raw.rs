@@ -0,0 +1,152 @@
+/// The parts of an `http(s)` url string, found without parsing it into a [`url::Url`].
+///
+/// Only urls that are already in the form [`url::Url`] would serialize them to are accepted,
+/// so anything that is matched against the parts (eg. [`crate::M::Domain`]) gives the same
+/// result as it would with the parsed url.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct RawUrl<'a> {
+    pub(crate) host: &'a str,
+    /// `host[:port]/path`, see [`crate::host_path`].
+    pub(crate) host_path: &'a str,
+    pub(crate) query: Option<&'a str>,
+    pub(crate) fragment: Option<&'a str>,
+}
+
+impl<'a> RawUrl<'a> {
+    /// Returns `None` when the input is not an `http(s)` url or when parsing it
+    /// could change it (uppercase host, default port, `..` segments, characters that
+    /// would be percent-encoded ...).
+    pub(crate) fn parse(input: &'a str) -> Option<Self> {
+        let (scheme, rest) = input.split_once("://")?;
+        let default_port = match scheme {
+            "http" => "80",
+            "https" => "443",
+            _ => return None,
+        };
+        if !rest.bytes().all(is_unchanged_by_parsing) {
+            return None;
+        }
+
+        let (rest, fragment) = match rest.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (rest, None),
+        };
+        let (host_path, query) = match rest.split_once('?') {
+            Some((host_path, query)) => (host_path, Some(query)),
+            None => (rest, None),
+        };
+        let (authority, path) = host_path.split_at(host_path.find('/')?);
+        let (host, port) = match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        };
+
+        if !is_normalized_domain(host)
+            || !port.is_none_or(|p| is_normalized_port(p, default_port))
+            || !is_normalized_path(path)
+        {
+            return None;
+        }
+
+        Some(Self {
+            host,
+            host_path,
+            query,
+            fragment,
+        })
+    }
+}
+
+/// Unreserved and reserved characters of RFC 3986, except for `'` that is
+/// percent-encoded in the query of `http(s)` urls.
+fn is_unchanged_by_parsing(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~:/?#[]@!$&()*+,;=%".contains(&b)
+}
+
+fn is_normalized_domain(host: &str) -> bool {
+    let labels_ok = host.split('.').all(|label| {
+        !label.is_empty()
+            // Punycode labels are decoded and validated, and can be rejected.
+            && !label.starts_with("xn--")
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+    });
+    // Numeric (`1`) or hexadecimal (`0x1`) last label is parsed as an IPv4 address.
+    let last_label_ok = host
+        .rsplit('.')
+        .next()
+        .is_some_and(|l| !l.bytes().all(|b| b.is_ascii_digit()) && !l.starts_with("0x"));
+
+    labels_ok && last_label_ok
+}
+
+fn is_normalized_port(port: &str, default_port: &str) -> bool {
+    !port.is_empty()
+        && !port.starts_with('0')
+        && port.bytes().all(|b| b.is_ascii_digit())
+        && port.parse::<u16>().is_ok()
+        && port != default_port
+}
+
+fn is_normalized_path(path: &str) -> bool {
+    !path.contains("%2e")
+        && !path.contains("%2E")
+        && path
+            .split('/')
+            .all(|segment| segment != "." && segment != "..")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_normalized_urls() {
+        assert_eq!(
+            RawUrl::parse("https://www.example.com:8443/a/b?x=1&y#frag"),
+            Some(RawUrl {
+                host: "www.example.com",
+                host_path: "www.example.com:8443/a/b",
+                query: Some("x=1&y"),
+                fragment: Some("frag"),
+            })
+        );
+        assert_eq!(
+            RawUrl::parse("http://example.com/#a?b"),
+            Some(RawUrl {
+                host: "example.com",
+                host_path: "example.com/",
+                query: None,
+                fragment: Some("a?b"),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_urls_that_parsing_would_change() {
+        for input in [
+            "ftp://example.com/",
+            "HTTPS://example.com/",
+            "https://Example.com/",
+            "https://example.com",
+            "https://example.com:443/",
+            "https://example.com:/",
+            "https://user@example.com/",
+            "https://127.0.0.1/",
+            "https://[::1]/",
+            "https://example..com/",
+            "https://example.com/a/../b",
+            "https://example.com/%2e/b",
+            "https://example.com/a b",
+            "https://example.com/?q='a'",
+            "https://example.com/\\a",
+            "https://exämple.com/",
+            "https://xn--/?a=1",
+            "https://xn--pple-43d.com/",
+            "https://example.0x1f/",
+        ] {
+            assert_eq!(RawUrl::parse(input), None, "{input}");
+        }
+    }
+}
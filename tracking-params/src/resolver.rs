@@ -0,0 +1,403 @@
+//! Follows the redirects of url shorteners (`bit.ly`, `t.co`, `lnkd.in`...) before cleaning.
+//!
+//! Enabled with the `resolver` feature.
+//!
+//! The destination of a short link is only known to the shortener, so the only way to clean
+//! it is to ask the shortener. [`HttpResolver`] does that while leaking as little as possible:
+//! * only hosts in [`ResolverOptions::allow`] are ever requested, the destination itself is never requested.
+//! * redirects are followed manually and every hop is cleaned before it's requested.
+//! * no cookies are stored or sent and no `Referer` header is sent.
+//!
+//! ```rust,no_run
+//! use tracking_params::resolver::{HttpResolver, Resolver, ResolverOptions};
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let resolver = HttpResolver::new(ResolverOptions::default())?;
+//! let cleaned = resolver.resolve(url::Url::parse("https://bit.ly/3AbCdEf")?).await?;
+//!
+//! println!("{}", cleaned.to_string());
+//! # Ok(())
+//! # }
+//! ```
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use async_trait::async_trait;
+use reqwest::{header::LOCATION, redirect::Policy, StatusCode};
+use url::Url;
+
+use crate::{clean, Cleaned};
+
+/// Hosts of well known url shorteners.
+pub const SHORTENERS: &[&str] = &[
+    "amzn.to",
+    "bit.ly",
+    "buff.ly",
+    "dlvr.it",
+    "goo.gl",
+    "is.gd",
+    "lnkd.in",
+    "ow.ly",
+    "rb.gy",
+    "rebrand.ly",
+    "shorturl.at",
+    "t.co",
+    "t.ly",
+    "tiny.cc",
+    "tinyurl.com",
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum ResolveError {
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Invalid redirect location: {0}")]
+    InvalidLocation(#[from] url::ParseError),
+    #[error("Too many redirects (more than {0})")]
+    TooManyHops(usize),
+}
+
+/// Resolves a (short) link to its destination.
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    /// Follows the redirects of `url` and returns the cleaned destination.
+    ///
+    /// Urls that this resolver doesn't handle are cleaned and returned as is.
+    async fn resolve(&self, url: Url) -> Result<Cleaned, ResolveError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolverOptions {
+    /// Maximum number of redirects to follow.
+    pub max_hops: usize,
+    /// Timeout of each request.
+    pub timeout: Duration,
+    /// Hosts (and their subdomains) whose redirects are followed.
+    pub allow: Vec<String>,
+    /// Hosts (and their subdomains) that are never requested, even when they are in `allow`.
+    pub deny: Vec<String>,
+    /// Number of resolved links to keep in memory, `0` disables the cache.
+    pub cache_size: usize,
+}
+
+impl Default for ResolverOptions {
+    fn default() -> Self {
+        Self {
+            max_hops: 5,
+            timeout: Duration::from_secs(5),
+            allow: SHORTENERS.iter().map(|s| s.to_string()).collect(),
+            deny: vec![],
+            cache_size: 1024,
+        }
+    }
+}
+
+/// [`Resolver`] that follows redirects by sending `HEAD` requests
+/// (falling back to `GET` when `HEAD` is not supported).
+pub struct HttpResolver {
+    client: reqwest::Client,
+    options: ResolverOptions,
+    cache: Mutex<HashMap<String, Url>>,
+}
+
+impl HttpResolver {
+    pub fn new(options: ResolverOptions) -> Result<Self, ResolveError> {
+        // `reqwest` never stores cookies unless built with a cookie store.
+        let client = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .referer(false)
+            .timeout(options.timeout)
+            .build()?;
+
+        Ok(Self {
+            client,
+            options,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn should_follow(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) if matches!(url.scheme(), "http" | "https") => host,
+            _ => return false,
+        };
+
+        !self.options.deny.iter().any(|d| is_same_site(host, d))
+            && self.options.allow.iter().any(|d| is_same_site(host, d))
+    }
+
+    /// Returns where `url` redirects to, if it does.
+    async fn next_hop(&self, url: &Url) -> Result<Option<Url>, ResolveError> {
+        let mut response = self.client.head(url.clone()).send().await?;
+        if matches!(
+            response.status(),
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+        ) {
+            response = self.client.get(url.clone()).send().await?;
+        }
+
+        if !response.status().is_redirection() {
+            return Ok(None);
+        }
+
+        match response
+            .headers()
+            .get(LOCATION)
+            .and_then(|l| l.to_str().ok())
+        {
+            Some(location) => Ok(Some(url.join(location)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn cached(&self, key: &str) -> Option<Url> {
+        self.cache.lock().ok()?.get(key).cloned()
+    }
+
+    fn store(&self, key: String, url: Url) {
+        if self.options.cache_size == 0 {
+            return;
+        }
+
+        if let Ok(mut cache) = self.cache.lock() {
+            if cache.len() >= self.options.cache_size {
+                if let Some(evicted) = cache.keys().next().cloned() {
+                    cache.remove(&evicted);
+                }
+            }
+            cache.insert(key, url);
+        }
+    }
+}
+
+#[async_trait]
+impl Resolver for HttpResolver {
+    async fn resolve(&self, url: Url) -> Result<Cleaned, ResolveError> {
+        let key = url.to_string();
+        if let Some(found) = self.cached(&key) {
+            return Ok(clean(found));
+        }
+
+        let mut url = clean(url).result;
+        let mut hops = 0;
+        while self.should_follow(&url) {
+            if hops == self.options.max_hops {
+                return Err(ResolveError::TooManyHops(self.options.max_hops));
+            }
+
+            match self.next_hop(&url).await? {
+                Some(next) => url = clean(next).result,
+                None => break,
+            }
+            hops += 1;
+        }
+
+        self.store(key, url.clone());
+
+        Ok(clean(url))
+    }
+}
+
+/// `host` is `domain` or one of its subdomains.
+fn is_same_site(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+    };
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    type Requests = Arc<Mutex<Vec<String>>>;
+
+    /// Starts a http server that stands in for url shorteners.
+    ///
+    /// `routes` maps `METHOD /path?query` to the status line and headers of the response,
+    /// anything else gets a `200 OK`.
+    async fn mock_server(routes: Vec<(&'static str, String)>) -> (SocketAddr, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let routes = routes.into_iter().collect::<HashMap<_, _>>();
+        let requests = Requests::default();
+
+        let seen = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = socket.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let route = request.split(" HTTP/").next().unwrap_or_default();
+
+                let response = routes
+                    .get(route)
+                    .cloned()
+                    .unwrap_or_else(|| "200 OK".to_string());
+                seen.lock().unwrap().push(request);
+
+                let _ = socket
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {response}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        )
+                        .as_bytes(),
+                    )
+                    .await;
+            }
+        });
+
+        (addr, requests)
+    }
+
+    fn local_options() -> ResolverOptions {
+        ResolverOptions {
+            allow: vec!["127.0.0.1".to_string()],
+            timeout: Duration::from_secs(2),
+            ..Default::default()
+        }
+    }
+
+    async fn resolve(options: ResolverOptions, url: &str) -> Result<Cleaned, ResolveError> {
+        HttpResolver::new(options)
+            .unwrap()
+            .resolve(Url::parse(url).unwrap())
+            .await
+    }
+
+    #[tokio::test]
+    async fn follows_and_cleans_every_hop() {
+        let (addr, requests) = mock_server(vec![
+            (
+                "HEAD /a",
+                "301 Moved Permanently\r\nLocation: /b?utm_source=short&id=1\r\nSet-Cookie: uid=1"
+                    .into(),
+            ),
+            (
+                "HEAD /b?id=1",
+                "302 Found\r\nLocation: https://www.example.com/page?utm_source=x&fbclid=y".into(),
+            ),
+        ])
+        .await;
+
+        let cleaned = resolve(local_options(), &format!("http://{addr}/a?utm_medium=x"))
+            .await
+            .unwrap();
+
+        assert_eq!(cleaned.to_string(), "https://www.example.com/page");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2, "the destination is never requested");
+        assert!(requests[0].starts_with("HEAD /a HTTP/1.1"));
+        assert!(requests[1].starts_with("HEAD /b?id=1 HTTP/1.1"));
+        for request in requests.iter() {
+            let request = request.to_lowercase();
+            assert!(!request.contains("\r\ncookie:"), "{request}");
+            assert!(!request.contains("\r\nreferer:"), "{request}");
+        }
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_get() {
+        let (addr, requests) = mock_server(vec![
+            ("HEAD /a", "405 Method Not Allowed".into()),
+            (
+                "GET /a",
+                "301 Moved Permanently\r\nLocation: https://www.example.com/".into(),
+            ),
+        ])
+        .await;
+
+        let cleaned = resolve(local_options(), &format!("http://{addr}/a"))
+            .await
+            .unwrap();
+
+        assert_eq!(cleaned.to_string(), "https://www.example.com/");
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn stops_after_max_hops() {
+        let (addr, requests) =
+            mock_server(vec![("HEAD /loop", "302 Found\r\nLocation: /loop".into())]).await;
+        let options = ResolverOptions {
+            max_hops: 3,
+            ..local_options()
+        };
+
+        let result = resolve(options, &format!("http://{addr}/loop")).await;
+
+        assert!(matches!(result, Err(ResolveError::TooManyHops(3))));
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn only_requests_allowed_hosts() {
+        let (addr, requests) = mock_server(vec![]).await;
+        let denied = ResolverOptions {
+            deny: vec!["127.0.0.1".to_string()],
+            ..local_options()
+        };
+
+        for options in [ResolverOptions::default(), denied] {
+            let cleaned = resolve(options, &format!("http://{addr}/a?utm_source=x"))
+                .await
+                .unwrap();
+
+            assert_eq!(cleaned.to_string(), format!("http://{addr}/a"));
+        }
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn caches_resolved_links() {
+        let (addr, requests) = mock_server(vec![(
+            "HEAD /a",
+            "301 Moved Permanently\r\nLocation: https://www.example.com/".into(),
+        )])
+        .await;
+        let resolver = HttpResolver::new(local_options()).unwrap();
+        let url = Url::parse(&format!("http://{addr}/a")).unwrap();
+
+        for _ in 0..2 {
+            let cleaned = resolver.resolve(url.clone()).await.unwrap();
+            assert_eq!(cleaned.to_string(), "https://www.example.com/");
+        }
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+        let options = ResolverOptions {
+            timeout: Duration::from_millis(200),
+            ..local_options()
+        };
+
+        let result = resolve(options, &format!("http://{addr}/a")).await;
+
+        assert!(matches!(result, Err(ResolveError::Http(e)) if e.is_timeout()));
+    }
+
+    #[test]
+    fn same_site() {
+        assert!(is_same_site("bit.ly", "bit.ly"));
+        assert!(is_same_site("www.bit.ly", "bit.ly"));
+        assert!(!is_same_site("orbit.ly", "bit.ly"));
+    }
+}
@@ -0,0 +1,482 @@
+//! Rules loaded from and written to JSON files, see [`RuleSet::leak_from_json`]
+//! and [`RuleSet::to_json`].
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Category, Decoding, HandlerSpec, LintKind, Rule, RuleMeta, RuleSet, M};
+
+#[derive(thiserror::Error, Debug)]
+pub enum RuleFileError {
+    #[error("Invalid rule file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Unknown category `{category}` in rule `{rule}`")]
+    UnknownCategory { rule: String, category: String },
+    #[error("Unknown lint `{lint}` in rule `{rule}`")]
+    UnknownLint { rule: String, lint: String },
+    #[error("The handler of rule `{rule}` is a closure, it can't be written to a rule file")]
+    CustomHandler { rule: String },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    #[serde(default)]
+    version: String,
+    rules: Vec<RuleSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    sources: Vec<String>,
+    #[serde(default)]
+    added_in: String,
+    deprecated: Option<String>,
+    hosts: Vec<MatcherSpec>,
+    #[serde(default)]
+    params: Vec<MatcherSpec>,
+    /// Params of other categories than `category`, by category name.
+    #[serde(default)]
+    params_in: BTreeMap<String, Vec<MatcherSpec>>,
+    #[serde(default)]
+    preserve: Vec<MatcherSpec>,
+    #[serde(default = "default_category")]
+    category: String,
+    #[serde(default)]
+    order_insensitive: bool,
+    #[serde(default)]
+    examples: Vec<(String, String)>,
+    handler: Option<HandlerStep>,
+    /// Names of the lints not to report, see [`Rule::allowing_lint`].
+    #[serde(default)]
+    allow: Vec<String>,
+}
+
+/// [`M`] with owned strings, eg. `"any"`, `{"exact": "ref"}` or `{"contains_all": ["a", "b"]}`.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MatcherSpec {
+    Any,
+    AllBut(String),
+    ContainsAll(Vec<String>),
+    Exact(String),
+    StartsWith(String),
+    Contains(String),
+    Domain(String),
+}
+
+/// [`HandlerSpec`] with owned strings, eg. `{"extract_from_query": {"keys": ["url"]}}`.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum HandlerStep {
+    ExtractFromQuery {
+        keys: Vec<String>,
+        #[serde(default)]
+        path_contains: Vec<String>,
+        #[serde(default)]
+        decode: Vec<DecodingSpec>,
+    },
+    ExtractFromPathSuffix {
+        after: String,
+        #[serde(default)]
+        decode: Vec<DecodingSpec>,
+    },
+    RewriteHost {
+        from: String,
+        to: String,
+    },
+    StripPath(MatcherSpec),
+    Chain(Vec<HandlerStep>),
+}
+
+/// [`Decoding`], eg. `"percent"` or `{"jwt": "url"}`.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum DecodingSpec {
+    Percent,
+    Base64,
+    Jwt(String),
+}
+
+fn default_category() -> String {
+    Category::Analytics.name().to_string()
+}
+
+/// Rules borrow `'static` strings, the strings of a rule file live until the program exits.
+fn leak(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+fn leak_slice<T>(items: Vec<T>) -> &'static [T] {
+    Box::leak(items.into_boxed_slice())
+}
+
+impl From<MatcherSpec> for M {
+    fn from(spec: MatcherSpec) -> Self {
+        match spec {
+            MatcherSpec::Any => M::Any,
+            MatcherSpec::AllBut(c) => M::AllBut(leak(c)),
+            MatcherSpec::ContainsAll(all) => M::ContainsAll(all.into_iter().map(leak).collect()),
+            MatcherSpec::Exact(e) => M::Exact(leak(e)),
+            MatcherSpec::StartsWith(sw) => M::StartsWith(leak(sw)),
+            MatcherSpec::Contains(c) => M::Contains(leak(c)),
+            MatcherSpec::Domain(d) => M::Domain(leak(d)),
+        }
+    }
+}
+
+impl From<DecodingSpec> for Decoding {
+    fn from(spec: DecodingSpec) -> Self {
+        match spec {
+            DecodingSpec::Percent => Decoding::Percent,
+            DecodingSpec::Base64 => Decoding::Base64,
+            DecodingSpec::Jwt(claim) => Decoding::Jwt(leak(claim)),
+        }
+    }
+}
+
+impl From<HandlerStep> for HandlerSpec {
+    fn from(step: HandlerStep) -> Self {
+        let decodings =
+            |decode: Vec<DecodingSpec>| decode.into_iter().map(Decoding::from).collect();
+        match step {
+            HandlerStep::ExtractFromQuery {
+                keys,
+                path_contains,
+                decode,
+            } => HandlerSpec::ExtractFromQuery {
+                keys: keys.into_iter().map(leak).collect(),
+                path_contains: path_contains.into_iter().map(leak).collect(),
+                decode: decodings(decode),
+            },
+            HandlerStep::ExtractFromPathSuffix { after, decode } => {
+                HandlerSpec::ExtractFromPathSuffix {
+                    after: leak(after),
+                    decode: decodings(decode),
+                }
+            }
+            HandlerStep::RewriteHost { from, to } => HandlerSpec::RewriteHost {
+                from: leak(from),
+                to: leak(to),
+            },
+            HandlerStep::StripPath(matcher) => HandlerSpec::StripPath(matcher.into()),
+            HandlerStep::Chain(steps) => {
+                HandlerSpec::Chain(steps.into_iter().map(HandlerSpec::from).collect())
+            }
+        }
+    }
+}
+
+fn matchers(specs: Vec<MatcherSpec>) -> Vec<M> {
+    specs.into_iter().map(M::from).collect()
+}
+
+/// A [`RuleSpec`] whose category and lint names are known, converting it to a [`Rule`]
+/// can't fail anymore.
+struct CheckedRule {
+    spec: RuleSpec,
+    category: Category,
+    params_in: Vec<(Category, Vec<MatcherSpec>)>,
+    allowed_lints: Vec<LintKind>,
+}
+
+impl TryFrom<RuleSpec> for CheckedRule {
+    type Error = RuleFileError;
+
+    fn try_from(mut spec: RuleSpec) -> Result<Self, Self::Error> {
+        let id = spec.id.clone();
+        let category = |name: &str| {
+            Category::from_name(name).ok_or_else(|| RuleFileError::UnknownCategory {
+                rule: id.clone(),
+                category: name.to_string(),
+            })
+        };
+        let rule_category = category(&spec.category)?;
+        let params_in = std::mem::take(&mut spec.params_in)
+            .into_iter()
+            .map(|(name, params)| Ok((category(&name)?, params)))
+            .collect::<Result<Vec<_>, RuleFileError>>()?;
+        let allowed_lints = spec
+            .allow
+            .iter()
+            .map(|name| {
+                LintKind::from_name(name).ok_or_else(|| RuleFileError::UnknownLint {
+                    rule: spec.id.clone(),
+                    lint: name.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            spec,
+            category: rule_category,
+            params_in,
+            allowed_lints,
+        })
+    }
+}
+
+impl From<CheckedRule> for Rule {
+    fn from(checked: CheckedRule) -> Self {
+        let spec = checked.spec;
+        let mut rule = Rule::new(matchers(spec.hosts), matchers(spec.params))
+            .with_meta(RuleMeta {
+                id: leak(spec.id),
+                description: leak(spec.description),
+                sources: leak_slice(spec.sources.into_iter().map(leak).collect()),
+                added_in: leak(spec.added_in),
+                deprecated: spec.deprecated.map(leak),
+            })
+            .preserving(matchers(spec.preserve))
+            .with_category(checked.category)
+            .with_examples(leak_slice(
+                spec.examples
+                    .into_iter()
+                    .map(|(input, expected)| (leak(input), leak(expected)))
+                    .collect(),
+            ));
+        for (category, params) in checked.params_in {
+            rule = rule.params_in(category, matchers(params));
+        }
+        if spec.order_insensitive {
+            rule = rule.order_insensitive();
+        }
+        for kind in checked.allowed_lints {
+            rule = rule.allowing_lint(kind);
+        }
+        if let Some(handler) = spec.handler {
+            rule = rule.with_handler_spec(handler.into());
+        }
+        rule
+    }
+}
+
+pub(crate) fn leak_from_json(json: &str) -> Result<RuleSet, RuleFileError> {
+    let file: RuleFile = serde_json::from_str(json)?;
+    let checked = file
+        .rules
+        .into_iter()
+        .map(CheckedRule::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Nothing is leaked until the whole file is known to be valid.
+    let rules = checked.into_iter().map(Rule::from).collect();
+    Ok(RuleSet::new(rules).with_version(leak(file.version)))
+}
+
+/// A [`Rule`] written the way [`RuleSpec`] reads it, defaults are left out.
+#[derive(Serialize)]
+struct RuleOut<'a> {
+    #[serde(skip_serializing_if = "str::is_empty")]
+    id: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    description: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    sources: &'a [&'a str],
+    #[serde(skip_serializing_if = "str::is_empty")]
+    added_in: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deprecated: Option<&'a str>,
+    hosts: &'a [M],
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    params: Vec<&'a M>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    params_in: BTreeMap<&'static str, Vec<&'a M>>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    preserve: &'a [M],
+    category: &'static str,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    order_insensitive: bool,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    examples: &'a [(&'a str, &'a str)],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    handler: Option<&'a HandlerSpec>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    allow: Vec<&'static str>,
+}
+
+impl<'a> TryFrom<&'a Rule> for RuleOut<'a> {
+    type Error = RuleFileError;
+
+    fn try_from(rule: &'a Rule) -> Result<Self, Self::Error> {
+        let handler = match &rule.handler {
+            Some(_) => Some(
+                rule.handler_spec()
+                    .ok_or_else(|| RuleFileError::CustomHandler {
+                        rule: crate::meta::key(rule),
+                    })?,
+            ),
+            None => None,
+        };
+        let mut params_in = BTreeMap::<_, Vec<_>>::new();
+        for (param, category) in &rule.params {
+            if let Some(category) = category {
+                params_in.entry(category.name()).or_default().push(param);
+            }
+        }
+
+        Ok(Self {
+            id: rule.meta.id,
+            description: rule.meta.description,
+            sources: rule.meta.sources,
+            added_in: rule.meta.added_in,
+            deprecated: rule.meta.deprecated,
+            hosts: &rule.host_path,
+            params: rule
+                .params
+                .iter()
+                .filter(|(_, category)| category.is_none())
+                .map(|(param, _)| param)
+                .collect(),
+            params_in,
+            preserve: &rule.preserve,
+            category: rule.category.name(),
+            order_insensitive: rule.order_insensitive,
+            examples: rule.examples,
+            handler,
+            allow: rule.allowed_lints.iter().map(LintKind::name).collect(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct RuleFileOut<'a> {
+    version: &'a str,
+    rules: Vec<RuleOut<'a>>,
+}
+
+pub(crate) fn to_json(set: &RuleSet) -> Result<String, RuleFileError> {
+    let file = RuleFileOut {
+        version: set.version().version,
+        rules: set
+            .rules()
+            .iter()
+            .map(RuleOut::try_from)
+            .collect::<Result<_, _>>()?,
+    };
+    Ok(serde_json::to_string_pretty(&file)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = r#"{
+        "version": "2024.1",
+        "rules": [
+            {
+                "id": "example-share",
+                "description": "Example share ids",
+                "hosts": [{"domain": "example.com"}, {"contains_all": ["example.", "/share"]}],
+                "params": [{"exact": "share_id"}, {"starts_with": "sh_"}],
+                "params_in": {"affiliate": [{"exact": "aff"}]},
+                "preserve": [{"exact": "v"}],
+                "category": "social_share",
+                "allow": ["over_broad"],
+                "examples": [
+                    ["https://example.com/watch?v=1&share_id=2&sh_src=app&aff=3", "https://example.com/watch?v=1"]
+                ]
+            },
+            {
+                "id": "example-redirect",
+                "hosts": [{"domain": "out.example.com"}],
+                "handler": {"chain": [
+                    {"extract_from_query": {"keys": ["t"], "decode": ["percent", {"jwt": "url"}]}},
+                    {"strip_path": {"starts_with": "ref="}}
+                ]},
+                "examples": [
+                    ["https://out.example.com/?t=e30.eyJ1cmwiOiJodHRwczovL2V4YW1wbGUub3JnL3AvcmVmPTEifQ.", "https://example.org/p"]
+                ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn loads_rules() {
+        let set = RuleSet::leak_from_json(RULES).unwrap();
+        let rule = &set.rules()[0];
+
+        assert_eq!(set.version().version, "2024.1");
+        assert_eq!(rule.meta().id, "example-share");
+        assert_eq!(rule.category, Category::SocialShare);
+        assert_eq!(
+            rule.params().map(|(_, c)| c).collect::<Vec<_>>(),
+            [
+                Category::SocialShare,
+                Category::SocialShare,
+                Category::Affiliate
+            ]
+        );
+        assert_eq!(rule.host_path.len(), 2);
+        assert_eq!(rule.allowed_lints, [LintKind::OverBroad]);
+        assert_eq!(
+            set.rules()[1].handler_spec(),
+            Some(&HandlerSpec::Chain(vec![
+                HandlerSpec::ExtractFromQuery {
+                    keys: vec!["t"],
+                    path_contains: vec![],
+                    decode: vec![Decoding::Percent, Decoding::Jwt("url")],
+                },
+                HandlerSpec::StripPath(M::StartsWith("ref=")),
+            ]))
+        );
+        assert_eq!(set.verify(), Ok(()));
+    }
+
+    #[test]
+    fn writes_rules_that_load_back() {
+        let file = RuleSet::leak_from_json(RULES).unwrap();
+        for set in [&file, RuleSet::builtin()] {
+            let loaded = RuleSet::leak_from_json(&set.to_json().unwrap()).unwrap();
+
+            assert_eq!(loaded.version(), set.version());
+            assert_eq!(
+                format!("{:?}", loaded.rules()),
+                format!("{:?}", set.rules())
+            );
+            assert!(loaded
+                .rules()
+                .iter()
+                .zip(set.rules())
+                .all(|(a, b)| a.examples == b.examples));
+        }
+    }
+
+    #[test]
+    fn does_not_write_custom_handlers() {
+        let set = RuleSet::new(vec![
+            Rule::new(vec![M::Domain("example.com")], vec![]).with_handler(|url| url)
+        ]);
+
+        assert!(matches!(
+            set.to_json(),
+            Err(RuleFileError::CustomHandler { rule }) if rule == "example.com"
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        assert!(matches!(
+            RuleSet::leak_from_json(&RULES.replace("social_share", "social")),
+            Err(RuleFileError::UnknownCategory { category, .. }) if category == "social"
+        ));
+        assert!(matches!(
+            RuleSet::leak_from_json(&RULES.replace("\"affiliate\"", "\"affiliates\"")),
+            Err(RuleFileError::UnknownCategory { category, .. }) if category == "affiliates"
+        ));
+        assert!(matches!(
+            RuleSet::leak_from_json(&RULES.replace("over_broad", "over-broad")),
+            Err(RuleFileError::UnknownLint { lint, .. }) if lint == "over-broad"
+        ));
+        assert!(matches!(
+            RuleSet::leak_from_json(&RULES.replace("\"params\"", "\"parameters\"")),
+            Err(RuleFileError::Json(_))
+        ));
+    }
+}
@@ -0,0 +1,685 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use aho_corasick::AhoCorasick;
+use smallvec::SmallVec;
+use url::Url;
+
+use crate::{
+    canonical::canonicalize,
+    clean_hash_params, clean_pairs, clean_query_string, expand_short_url,
+    fragment::Fragment,
+    heuristic, host_path, lenient, lint,
+    meta::{self, content_hash},
+    nested::clean_nested_urls,
+    pii,
+    raw::RawUrl,
+    rules, schemes, verify, Categories, Category, CleanError, Cleaned, ExampleFailure, Lint,
+    Options, Rule, RuleSetDiff, RuleSetVersion, M,
+};
+
+/// A set of [`Rule`]s compiled for fast lookup.
+///
+/// Rules that only match [`M::Domain`]s are indexed by domain, so finding the rules
+/// for a url costs one hash map lookup per label of its host no matter how many rules
+/// there are. Rules with any other host matcher (eg. [`M::Contains`]) are kept in a
+/// (hopefully small) list that is checked one by one.
+///
+/// All [`M::Exact`], [`M::StartsWith`] and [`M::Contains`] params of all the rules are
+/// merged into a single automaton, so classifying a param costs the same no matter
+/// how many params the rules define.
+///
+/// ```rust
+/// use tracking_params::{Rule, RuleSet, M};
+///
+/// let rules = RuleSet::new(vec![Rule::new(
+///     vec![M::Domain("example.com")],
+///     vec![M::Exact("session")],
+/// )]);
+/// let url = url::Url::parse("https://www.example.com/?session=1&id=2")?;
+///
+/// assert_eq!(rules.clean(url).to_string(), "https://www.example.com/?id=2");
+/// # Ok::<_, url::ParseError>(())
+/// ```
+#[derive(Debug)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    /// Index of rules in `rules` by the domains they match.
+    by_domain: HashMap<&'static str, Vec<usize>>,
+    /// Index of rules in `rules` that can't be looked up by domain.
+    residual: Vec<usize>,
+    /// Params of all the rules that can be matched in a single pass.
+    params: AhoCorasick,
+    /// Anchoring and rules of each pattern in `params`, by pattern id.
+    patterns: Vec<Pattern>,
+    /// Params that can't be part of `params` (eg. [`M::AllBut`]), the index of their rule
+    /// and their category.
+    other_params: Vec<(usize, Category, M)>,
+    version: RuleSetVersion,
+}
+
+#[derive(Debug)]
+struct Pattern {
+    anchor: Anchor,
+    /// Sorted index of the rules that have this param, and its category in each of them.
+    rules: Vec<(usize, Category)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Anchor {
+    /// [`M::Exact`], the match must cover the whole key.
+    Both,
+    /// [`M::StartsWith`], the match must be at the start of the key.
+    Start,
+    /// [`M::Contains`], the match can be anywhere.
+    None,
+}
+
+/// Index of rules in a [`RuleSet`], inline for the handful of rules that usually apply
+/// to a url so that matching doesn't allocate.
+type RuleIds = SmallVec<[usize; 16]>;
+
+/// Rules of a [`RuleSet`] that apply to a url.
+pub(crate) struct Matched<'a> {
+    set: &'a RuleSet,
+    /// Sorted index of the rules.
+    ids: RuleIds,
+    /// The categories of params that are removed.
+    categories: Categories,
+}
+
+impl<'a> Matched<'a> {
+    /// The rules, in the order they were defined.
+    pub(crate) fn rules(&self) -> impl Iterator<Item = &'a Rule> + '_ {
+        self.ids.iter().map(|idx| &self.set.rules[*idx])
+    }
+
+    /// The same rules, only removing the params whose category is one of `categories`.
+    pub(crate) fn only(&self, categories: Categories) -> Self {
+        Self {
+            set: self.set,
+            ids: self.ids.clone(),
+            categories,
+        }
+    }
+
+    /// Whether any of the rules removes the param `key`.
+    pub(crate) fn removes(&self, key: &str) -> bool {
+        self.set.params.find_overlapping_iter(key).any(|m| {
+            let pattern = &self.set.patterns[m.pattern().as_usize()];
+            let anchored = match pattern.anchor {
+                Anchor::Both => m.start() == 0 && m.end() == key.len(),
+                Anchor::Start => m.start() == 0,
+                Anchor::None => true,
+            };
+
+            anchored
+                && pattern.rules.iter().any(|(rule, category)| {
+                    self.categories.contains(*category) && self.ids.binary_search(rule).is_ok()
+                })
+        }) || self.set.other_params.iter().any(|(rule, category, m)| {
+            self.categories.contains(*category)
+                && self.ids.binary_search(rule).is_ok()
+                && m.matches_str(Some(key))
+        })
+    }
+
+    /// Whether any of the rules needs the param `key`, either listed in [`Rule::preserving`]
+    /// or the one kept by an [`M::AllBut`]. Not filtered by [`Matched::only`] since
+    /// the params are needed whatever categories are cleaned.
+    pub(crate) fn preserves(&self, key: &str) -> bool {
+        self.rules().any(|rule| {
+            rule.preserve.iter().any(|m| m.matches_str(Some(key)))
+                || rule
+                    .params()
+                    .any(|(m, _)| matches!(m, M::AllBut(_)) && !m.matches_str(Some(key)))
+        })
+    }
+}
+
+impl RuleSet {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        let mut by_domain: HashMap<&'static str, Vec<usize>> = HashMap::new();
+        let mut residual = vec![];
+        let mut needles = vec![];
+        let mut patterns: Vec<Pattern> = vec![];
+        let mut pattern_ids = HashMap::new();
+        let mut other_params = vec![];
+
+        for (idx, rule) in rules.iter().enumerate() {
+            for (param, category) in rule.params() {
+                let (needle, anchor) = match param {
+                    M::Exact(e) => (*e, Anchor::Both),
+                    M::StartsWith(sw) => (*sw, Anchor::Start),
+                    M::Contains(c) => (*c, Anchor::None),
+                    other => {
+                        other_params.push((idx, category, other.clone()));
+                        continue;
+                    }
+                };
+
+                let id = *pattern_ids.entry((needle, anchor)).or_insert_with(|| {
+                    needles.push(needle);
+                    patterns.push(Pattern {
+                        anchor,
+                        rules: vec![],
+                    });
+                    patterns.len() - 1
+                });
+                if !patterns[id].rules.contains(&(idx, category)) {
+                    patterns[id].rules.push((idx, category));
+                }
+            }
+
+            let domains = rule
+                .host_path
+                .iter()
+                .map(|m| match m {
+                    M::Domain(d) => Some(*d),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+
+            match domains {
+                Some(domains) if !domains.is_empty() => {
+                    for domain in domains {
+                        by_domain.entry(domain).or_default().push(idx);
+                    }
+                }
+                _ => residual.push(idx),
+            }
+        }
+
+        Self {
+            version: RuleSetVersion {
+                version: "",
+                hash: content_hash(&rules),
+            },
+            rules,
+            by_domain,
+            residual,
+            params: AhoCorasick::new(needles).expect("build param automaton"),
+            patterns,
+            other_params,
+        }
+    }
+
+    /// The rules shipped with this crate, used by [`crate::clean`] and friends.
+    pub fn builtin() -> &'static RuleSet {
+        &rules::GLOBAL_PARAMS
+    }
+
+    /// Loads the rules of a JSON file, their examples can be checked with [`RuleSet::verify`].
+    ///
+    /// ```json
+    /// {
+    ///     "version": "2024.1",
+    ///     "rules": [{
+    ///         "id": "example-share",
+    ///         "hosts": [{"domain": "example.com"}],
+    ///         "params": [{"exact": "share_id"}, {"starts_with": "sh_"}],
+    ///         "category": "social_share",
+    ///         "examples": [["https://example.com/?share_id=1", "https://example.com/"]]
+    ///     }]
+    /// }
+    /// ```
+    ///
+    /// Matchers are written like [`M`] in snake case (`"any"`, `{"contains_all": ["a", "b"]}`),
+    /// categories by their [`crate::Category::name`]. The params of other categories than the
+    /// one of the rule are listed by category in `params_in` (`{"affiliate": [{"exact": "tag"}]}`).
+    /// Lints not to report for a rule are listed by [`crate::LintKind::name`] in `allow`.
+    ///
+    /// Rules borrow `'static` strings so **the strings of the file are leaked**: they live
+    /// until the program exits, rule files are meant to be loaded once at startup. Nothing
+    /// is leaked when the file is invalid.
+    #[cfg(feature = "rule-files")]
+    pub fn leak_from_json(json: &str) -> Result<Self, crate::RuleFileError> {
+        crate::rule_file::leak_from_json(json)
+    }
+
+    /// Writes the rules in the format read by [`RuleSet::leak_from_json`], fails for rules
+    /// with a closure handler ([`Rule::with_handler`]).
+    #[cfg(feature = "rule-files")]
+    pub fn to_json(&self) -> Result<String, crate::RuleFileError> {
+        crate::rule_file::to_json(self)
+    }
+
+    /// Sets the version reported with the content hash of the rules, see [`RuleSetVersion`].
+    pub fn with_version(mut self, version: &'static str) -> Self {
+        self.version.version = version;
+        self
+    }
+
+    pub fn version(&self) -> RuleSetVersion {
+        self.version
+    }
+
+    /// The rules, in the order they were defined.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// The changes from this set to `newer`, eg. to write the changelog of a release.
+    pub fn diff(&self, newer: &RuleSet) -> RuleSetDiff {
+        meta::diff(self, newer)
+    }
+
+    /// Cleans the examples of every rule (see [`Rule::with_examples`]) and returns the ones
+    /// that are not cleaned as expected or that the rule doesn't apply to.
+    pub fn verify(&self) -> Result<(), Vec<ExampleFailure>> {
+        verify::verify(self)
+    }
+
+    /// Problems in the rules (duplicates, shadowed or unreachable rules...), see [`Lint`].
+    pub fn lint(&self) -> Vec<Lint> {
+        lint::lint(self)
+    }
+
+    /// Like [`RuleSet::lint`], and also reports the rules with a handler that none of
+    /// `tested_urls` is cleaned with.
+    pub fn lint_with_tests(&self, tested_urls: &[&str]) -> Vec<Lint> {
+        let mut lints = self.lint();
+        lints.extend(lint::untested_handlers(self, tested_urls));
+        lints
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Removes tracking parameters from a given [`Url`] using the rules of this set.
+    pub fn clean(&self, url: Url) -> Cleaned {
+        self.clean_with(url, &Options::default())
+    }
+
+    /// Same as [`RuleSet::clean`] but with the given [`Options`].
+    pub fn clean_with(&self, url: Url, options: &Options) -> Cleaned {
+        let (mut cleaned, order_insensitive) = match url.scheme() {
+            "http" | "https" | "ftp" | "ws" | "wss" => self.clean_web(url, options),
+            // Android intents open an app or fall back to a web page, only the latter is cleaned.
+            "intent" => (
+                Cleaned::new(schemes::clean_intent(url, self, options)),
+                false,
+            ),
+            "mailto" => (
+                Cleaned::new(schemes::clean_mailto(url, self, options)),
+                false,
+            ),
+            "market" => (Cleaned::new(schemes::clean_market(url)), false),
+            // The rules are written for web pages, the params of apps (`slack://`, `tel:`...)
+            // mean something else and are kept as is.
+            _ => (Cleaned::new(url), false),
+        };
+
+        if options.canonicalize.is_enabled() {
+            (cleaned.result, cleaned.canonical_steps) =
+                canonicalize(cleaned.result, &options.canonicalize, order_insensitive);
+        }
+        cleaned.rule_set_version = self.version;
+
+        cleaned
+    }
+
+    /// Cleans an url of a web scheme (`http(s)`, `ftp` and `ws(s)`), returns whether
+    /// the matching rules are order insensitive.
+    fn clean_web(&self, url: Url, options: &Options) -> (Cleaned, bool) {
+        let mut url = url;
+        let mut handlers_used = 0;
+        // Handlers can return a url other rules apply to (eg. the destination of a redirector
+        // is a short url or another redirector), so the rules of the url they return are
+        // applied until it doesn't change. Otherwise cleaning the result again would change it.
+        // Spec handlers only shorten urls or rewrite hosts so this ends, stopping at the first
+        // url seen twice in case handlers rewrite a url back and forth.
+        let mut seen = HashSet::new();
+        loop {
+            if options.expand_short_urls {
+                url = expand_short_url(url);
+            }
+
+            let handlers = self
+                .matching(&url)
+                .rules()
+                .filter_map(|r| r.handler.as_ref())
+                .collect::<Vec<_>>();
+            if handlers.is_empty() {
+                break;
+            }
+
+            seen.insert(url.clone());
+            for handler in handlers {
+                url = handler.apply(url);
+                handlers_used += 1;
+            }
+            if seen.contains(&url) {
+                break;
+            }
+        }
+
+        let all_rules = self.matching(&url);
+        let matched_rules = all_rules.only(options.categories);
+        let mut url = clean_hash_params(clean_query_string(url, &matched_rules), &matched_rules);
+        let mut pii_findings = vec![];
+        if let Some(policy) = options.pii {
+            (url, pii_findings) = pii::scrub(url, policy);
+        }
+        // After the params removed for personal data, so the positions of the heuristic
+        // removals are the ones of the returned query.
+        let mut heuristic_removals = vec![];
+        if options.aggressive {
+            (url, heuristic_removals) = heuristic::clean_query(url, &all_rules);
+        }
+        if options.nested_urls_depth > 0 {
+            url = clean_nested_urls(url, self, options);
+        }
+
+        let order_insensitive = all_rules.rules().any(|r| r.order_insensitive);
+        let cleaned = Cleaned {
+            handlers_used,
+            heuristic_removals,
+            pii_findings,
+            ..Cleaned::new(url)
+        };
+        (cleaned, order_insensitive)
+    }
+
+    /// Same as [`crate::clean_lenient`] but using the rules of this set.
+    pub fn clean_lenient(&self, url: &str) -> Result<Cleaned, CleanError> {
+        let (url, normalizations) = lenient::parse(url)?;
+
+        Ok(Cleaned {
+            normalizations,
+            ..self.clean(url)
+        })
+    }
+
+    /// Same as [`crate::clean_cow`] but using the rules of this set.
+    pub fn clean_cow<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, url::ParseError> {
+        if let Some(raw) = RawUrl::parse(url) {
+            let matched = self.matching_host_path(raw.host, raw.host_path);
+            // Handlers can do anything, they must be run on the parsed url.
+            if !matched.rules().any(|r| r.handler.is_some()) {
+                return Ok(clean_raw(url, &raw, &matched));
+            }
+        }
+
+        let cleaned = self.clean(Url::parse(url)?).to_string();
+        if cleaned == url {
+            Ok(Cow::Borrowed(url))
+        } else {
+            Ok(Cow::Owned(cleaned))
+        }
+    }
+
+    /// Same as [`crate::clean_batch`] but using the rules of this set.
+    pub fn clean_batch<I>(&self, urls: I) -> Vec<Result<Cleaned, url::ParseError>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str> + Send,
+    {
+        let clean = |url: I::Item| Url::parse(url.as_ref()).map(|url| self.clean(url));
+
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+
+            urls.into_iter()
+                .collect::<Vec<_>>()
+                .into_par_iter()
+                .map(clean)
+                .collect()
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            urls.into_iter().map(clean).collect()
+        }
+    }
+
+    /// Rules that apply to the given url.
+    pub(crate) fn matching(&self, url: &Url) -> Matched<'_> {
+        self.matching_host_path(url.host_str().unwrap_or_default(), host_path(url))
+    }
+
+    fn matching_host_path(&self, host: &str, host_path: &str) -> Matched<'_> {
+        let mut found = self
+            .residual
+            .iter()
+            .copied()
+            .filter(|idx| {
+                self.rules[*idx]
+                    .host_path
+                    .iter()
+                    .any(|m| m.matches_str(Some(host_path)))
+            })
+            .collect::<RuleIds>();
+
+        // `www.example.co.uk` -> `example.co.uk` -> `co.uk` -> `uk`, the trailing dot of a
+        // fully qualified host (`example.co.uk.`) is the same host.
+        let mut domain = host.strip_suffix('.').unwrap_or(host);
+        loop {
+            if let Some(ids) = self.by_domain.get(domain) {
+                found.extend(ids.iter().copied());
+            }
+            match domain.split_once('.') {
+                Some((_, parent)) => domain = parent,
+                None => break,
+            }
+        }
+
+        found.sort_unstable();
+        found.dedup();
+        Matched {
+            set: self,
+            ids: found,
+            categories: Categories::all(),
+        }
+    }
+}
+
+/// `url` cleaned by the `matched` rules without parsing it, the same as [`RuleSet::clean`]
+/// for the urls [`RawUrl::parse`] accepts when none of the rules has a handler.
+fn clean_raw<'a>(url: &'a str, raw: &RawUrl, matched: &Matched) -> Cow<'a, str> {
+    let query = raw.query.map(|query| clean_pairs(query, matched));
+    let fragment = raw
+        .fragment
+        .map(|fragment| Fragment::parse(fragment).clean(matched));
+    // Empty query and fragment are removed.
+    let unchanged = |part: &Option<Cow<str>>| {
+        part.as_ref()
+            .is_none_or(|part| matches!(part, Cow::Borrowed(p) if !p.is_empty()))
+    };
+    if unchanged(&query) && unchanged(&fragment) {
+        return Cow::Borrowed(url);
+    }
+
+    // The query and fragment are the first `?` and `#` of urls `RawUrl` accepts.
+    let mut cleaned = url.split(['?', '#']).next().unwrap_or_default().to_string();
+    for (separator, part) in [('?', query), ('#', fragment)] {
+        if let Some(part) = part.filter(|part| !part.is_empty()) {
+            cleaned.push(separator);
+            cleaned.push_str(&part);
+        }
+    }
+    Cow::Owned(cleaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HandlerSpec;
+
+    fn rule_set() -> RuleSet {
+        RuleSet::new(vec![
+            Rule::new(vec![M::Any], vec![M::Exact("any")]),
+            Rule::new(vec![M::Domain("example.com")], vec![M::Exact("domain")]),
+            Rule::new(vec![M::Contains("/shop")], vec![M::Exact("shop")]),
+            Rule::new(
+                vec![M::Domain("www.example.com"), M::Domain("example.org")],
+                vec![M::Exact("www")],
+            ),
+            Rule::new(vec![M::Domain("other.com")], vec![M::Exact("other")]),
+        ])
+    }
+
+    fn matching_params(rules: &RuleSet, url: &str) -> Vec<&'static str> {
+        rules
+            .matching(&Url::parse(url).unwrap())
+            .rules()
+            .flat_map(|r| r.params())
+            .map(|(p, _)| match p {
+                M::Exact(e) => *e,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn indexes_domain_rules() {
+        let rules = rule_set();
+
+        assert_eq!(rules.len(), 5);
+        assert_eq!(rules.residual, vec![0, 2]);
+        assert_eq!(rules.by_domain.get("example.com"), Some(&vec![1]));
+        assert_eq!(rules.by_domain.get("example.org"), Some(&vec![3]));
+    }
+
+    #[test]
+    fn matching_keeps_definition_order() {
+        let rules = rule_set();
+
+        assert_eq!(
+            matching_params(&rules, "https://www.example.com/shop"),
+            vec!["any", "domain", "shop", "www"]
+        );
+        assert_eq!(
+            matching_params(&rules, "https://a.b.example.org:8443/"),
+            vec!["any", "www"]
+        );
+        assert_eq!(
+            matching_params(&rules, "https://notexample.com/"),
+            vec!["any"]
+        );
+        assert_eq!(
+            matching_params(&rules, "https://www.example.com./shop"),
+            vec!["any", "domain", "shop", "www"],
+            "fully qualified host"
+        );
+    }
+
+    #[test]
+    fn removes_params_of_matched_rules_only() {
+        let rules = RuleSet::new(vec![
+            Rule::new(
+                vec![M::Any],
+                vec![M::Exact("utm"), M::StartsWith("mc_"), M::Contains("clid")],
+            ),
+            Rule::new(
+                vec![M::Domain("example.com")],
+                vec![M::Exact("ref"), M::AllBut("id")],
+            ),
+        ]);
+        let other = rules.matching(&Url::parse("https://other.com/").unwrap());
+        let example = rules.matching(&Url::parse("https://example.com/").unwrap());
+
+        assert!(other.removes("utm"));
+        assert!(!other.removes("utm_source"), "exact");
+        assert!(other.removes("mc_eid"));
+        assert!(!other.removes("xmc_eid"), "starts with");
+        assert!(other.removes("gclid"));
+        assert!(other.removes("clid"));
+        assert!(!other.removes("ref"), "rule of another domain");
+        assert!(!other.removes("page"), "rule of another domain");
+
+        assert!(example.removes("ref"));
+        assert!(example.removes("page"));
+        assert!(!example.removes("id"));
+    }
+
+    #[test]
+    fn removes_params_of_the_selected_categories_only() {
+        let rules = RuleSet::new(vec![Rule::new(
+            vec![M::Domain("example.com")],
+            vec![M::Exact("qid"), M::AllBut("id")],
+        )
+        .with_category(Category::SessionState)
+        .params_in(Category::Affiliate, vec![M::Exact("tag")])]);
+        let matched = rules.matching(&Url::parse("https://example.com/").unwrap());
+        let affiliate = matched.only(Categories::empty().with(Category::Affiliate));
+        let session = matched.only(Categories::empty().with(Category::SessionState));
+
+        assert!(affiliate.removes("tag"));
+        assert!(!affiliate.removes("qid"));
+        assert!(!affiliate.removes("page"), "all but of another category");
+        assert!(session.removes("qid"));
+        assert!(session.removes("page"));
+        assert!(!session.removes("id"));
+    }
+
+    #[test]
+    fn runs_handlers_until_the_url_does_not_change() {
+        let mut url = "https://example.org/page?utm_source=x".to_string();
+        for _ in 0..7 {
+            url = format!("https://www.google.com/url?q={}", urlencoding::encode(&url));
+        }
+        let cleaned = RuleSet::builtin().clean(Url::parse(&url).unwrap());
+
+        assert_eq!(cleaned.to_string(), "https://example.org/page");
+    }
+
+    #[test]
+    fn stops_handlers_rewriting_urls_back_and_forth() {
+        let rules = RuleSet::new(vec![
+            Rule::new(vec![M::Domain("a.example")], vec![M::Exact("ref")]).with_handler_spec(
+                HandlerSpec::RewriteHost {
+                    from: "a.example",
+                    to: "b.example",
+                },
+            ),
+            Rule::new(vec![M::Domain("b.example")], vec![]).with_handler_spec(
+                HandlerSpec::RewriteHost {
+                    from: "b.example",
+                    to: "a.example",
+                },
+            ),
+        ]);
+        let cleaned = rules.clean(Url::parse("https://a.example/?ref=1").unwrap());
+
+        assert_eq!(cleaned.to_string(), "https://a.example/");
+    }
+
+    #[test]
+    fn matching_does_not_allocate() {
+        for url in [
+            "https://www.amazon.co.uk/dp/B08N5WRWNW?tag=x",
+            "https://www.google.com/url?q=https://example.com/",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ] {
+            let matched = RuleSet::builtin().matching(&Url::parse(url).unwrap());
+
+            assert!(!matched.ids.spilled(), "{url}: {} rules", matched.ids.len());
+            assert!(!matched.only(Categories::all()).ids.spilled(), "{url}");
+        }
+    }
+
+    #[test]
+    fn preserves_params_of_matched_rules_only() {
+        let rules = RuleSet::new(vec![
+            Rule::new(vec![M::Domain("example.com")], vec![M::Exact("ref")])
+                .preserving(vec![M::StartsWith("list")]),
+            Rule::new(vec![M::Domain("example.org")], vec![M::AllBut("id")]),
+        ]);
+        let com = rules.matching(&Url::parse("https://example.com/").unwrap());
+        let org = rules.matching(&Url::parse("https://example.org/").unwrap());
+
+        assert!(com.preserves("list_id"));
+        assert!(!com.preserves("ref"));
+        assert!(!com.preserves("id"), "rule of another domain");
+        assert!(org.preserves("id"), "kept by all but");
+        assert!(!org.preserves("list_id"));
+    }
+}
@@ -7,194 +7,155 @@
 //! More sources:
 //! * [`https://maxchadwick.xyz/tracking-query-params-registry/`]
 //!
-use url::Url;
-
 use crate::{
-    Rule,
+    Category, Decoding, HandlerSpec, LintKind, Rule, RuleMeta, RuleSet, Service,
     M::{self, *},
 };
 
-lazy_static::lazy_static! {
-    pub(crate) static ref  GLOBAL_PARAMS: Vec<Rule> = vec![
-        Rule {
-            host_path: vec![Any],
-            params: UNIVERSAL_PARAMS.to_vec(),
-            handler: None
-        },
-        Rule {
-            host_path: vec![Contains("amazon")],
-            params: vec![
-                Exact("_encoding"),
-                Exact("creative"),
-                Exact("creativeASIN"),
-                Exact("dchild"),
-                Exact("ie"),
-                Exact("linkCode"),
-                Exact("linkId"),
-                Exact("orig"),
-                Exact("psc"),
-                Exact("qid"),
-                Exact("ref"),
-                Exact("refRID"),
-                Exact("sr"),
-                Exact("tag"),
-            ],
-            handler: Some(Box::new(|url| extract_link_from_query_string(url, vec!["U"], Some(vec!["gp/r.html" ]))))
-        },
-        Rule {
-            host_path: vec![Contains("track"), Contains("analytics")],
-            params: vec![],
-            handler: Some(Box::new(|url| extract_link_from_query_string(url, vec!["redir", "dest"], None)))
-        },
-        Rule {
-            host_path: vec![ContainsAll(vec!["linkedin.com", "messaging", "thread"])],
-            params: vec![AllBut("_")],
-            handler: None
-        },
-        Rule {
-            host_path: vec![Contains("bing")],
-            params: vec![
-                Exact("cvid"),
-                Exact("form"),
-                Exact("pq"),
-                Exact("qs"),
-                Exact("sc"),
-                Exact("sk"),
-                Exact("sp"),
-            ],
-            handler: None
-        },
-        Rule {
-            host_path: vec![Contains("google")],
-            params: vec![
-                Exact("cvid"),
-                Exact("ei"),
-                Exact("gws_rd"),
-                Exact("sei"),
-                Exact("ved"),
-            ],
-            handler: None
-        },
-        Rule {
-            host_path: vec![ContainsAll(vec!["google", "/url"])],
-            params: vec![
-                Exact("usg"),
-            ],
-            handler: Some(Box::new(|url| extract_link_from_query_string(url, vec!["q", "url"], None))),
-        },
+mod ecommerce;
+mod search;
+mod services;
+mod social;
 
-        Rule {
-            host_path: vec![Contains("instagram")],
-            params: vec![
-                Exact("igshid"),
-            ],
-            handler: None
-        },
-        Rule {
-            host_path: vec![Contains("nytimes")],
-            params: vec![
-                Exact("emc"),
-                Exact("partner"),
-            ],
-            handler: None
-        },
-        Rule {
-            host_path: vec![Contains("reddit")],
-            params: vec![
-                Exact("context"),
-                Exact("ref"),
-                Exact("ref_source"),
-                Exact("st"),
-            ],
-            handler: None
-        },
-        Rule {
-            host_path: vec![Contains("twitter")],
-            params: vec![
-                Exact("context"),
-                Exact("vertical"),
-                Exact("src"),
-                Exact("s"),
-                Exact("ref_src"),
-                Exact("ref_url"),
-            ],
-            handler: None
-        },
-        Rule {
-            host_path: vec![Contains("youtube")],
-            params: vec![
-                Contains("ab_channel"),
-                Contains("attr_tag"),
-                Contains("feature"),
-                Contains("kw"),
-            ],
-            handler: Some(Box::new(|url| extract_link_from_query_string(url, vec!["q"], Some(vec!["redirect" ]))))
-        },
-        // https://community.spotify.com/t5/Desktop-Windows/si-Parameter-in-Spotify-URL-s/td-p/4538290
-        Rule {
-            host_path: vec![Contains("spotify")],
-            params: vec![
-                Exact("si")
-            ],
-            handler: None
-        },
-        // https://partnerhelp.ebay.com/helpcenter/s/article/What-are-the-parameters-of-an-EPN-link#tracking-link-format
-        Rule {
-            host_path: vec![Contains("ebay")],
-            params: vec![
-                Exact("mkevt"),
-                Exact("mkcid"),
-                Exact("mkrid"),
-                Exact("campid"),
-                Exact("toolid"),
-                Exact("customid"),
-            ],
-            handler: None
-        },
-    ];
+const DCZYSZ: &str = "https://github.com/dczysz/tracking-params/blob/5ccb3f8e3d4d6f3dfb88abe85a304fb78cfa41ce/src/params.ts";
+const TRACKING_PARAMS_REGISTRY: &str = "https://maxchadwick.xyz/tracking-query-params-registry/";
+const CLEAR_URLS: &str = "https://github.com/ClearURLs/Rules/blob/master/data.min.json";
+/// The rules of the first releases of this crate, for params that no public list documents.
+const ORIGINAL_RULES: &str =
+    "https://github.com/mustakimali/privacy-redirect/blob/main/tracking-params/src/rules.rs";
+const ADGUARD: &str = "https://github.com/AdguardTeam/AdguardFilters/blob/master/TrackParamFilter/sections/specific.txt";
 
-    static ref UNIVERSAL_PARAMS: Vec<M> = vec![
-        Exact("__twitter_impression"),
-        Exact("_hsenc"),
+/// Services that can expand their short form urls.
+pub(crate) static SERVICES: &[&Service] =
+    &[&services::INSTAGRAM, &services::REDDIT, &services::YOUTUBE];
+
+/// Mobile hosts and their desktop counterpart, subdomains of the mobile host
+/// (eg. `en.m.wikipedia.org`) are mapped too.
+pub(crate) static DESKTOP_HOSTS: &[(&str, &str)] = &[
+    ("m.ebay.com", "www.ebay.com"),
+    ("m.facebook.com", "www.facebook.com"),
+    ("m.imdb.com", "www.imdb.com"),
+    ("m.twitch.tv", "www.twitch.tv"),
+    ("m.wikipedia.org", "wikipedia.org"),
+    ("m.youtube.com", "www.youtube.com"),
+    ("mobile.twitter.com", "twitter.com"),
+    ("mobile.x.com", "x.com"),
+];
+
+lazy_static::lazy_static! {
+    pub(crate) static ref  GLOBAL_PARAMS: RuleSet = RuleSet::new([vec![
+        Rule::new(vec![Any], ANALYTICS_PARAMS.to_vec())
+        .with_meta(RuleMeta {
+            id: "universal-analytics",
+            description: "Campaign attribution params of analytics tools (Google Analytics, Matomo, Piwik, Webtrends...)",
+            sources: &[DCZYSZ, TRACKING_PARAMS_REGISTRY],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::Analytics)
+        .with_examples(&[("https://example.com/post?id=1&utm_source=newsletter&utm_medium=email", "https://example.com/post?id=1")]),
+        Rule::new(vec![Any], CLICK_ID_PARAMS.to_vec())
+        .with_meta(RuleMeta {
+            id: "universal-click-ids",
+            description: "Ad click identifiers of Google, Microsoft, Meta, Yandex and HubSpot ads",
+            sources: &[DCZYSZ, TRACKING_PARAMS_REGISTRY],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::ClickId)
+        .with_examples(&[("https://example.com/shop?gclid=Cj0KCQiA&item=42&msclkid=abc", "https://example.com/shop?item=42")]),
+        Rule::new(vec![Any], EMAIL_MARKETING_PARAMS.to_vec())
+        .with_meta(RuleMeta {
+            id: "universal-email-marketing",
+            description: "Recipient and campaign ids of newsletters (Mailchimp, HubSpot, Marketo, Listrak...)",
+            sources: &[DCZYSZ, TRACKING_PARAMS_REGISTRY],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::EmailMarketing)
+        .with_examples(&[("https://example.com/news?mc_cid=a1b2&mc_eid=c3d4", "https://example.com/news")]),
+        Rule::new(vec![Any], SOCIAL_SHARE_PARAMS.to_vec())
+        .with_meta(RuleMeta {
+            id: "universal-social-share",
+            description: "Facebook and Twitter share attribution",
+            sources: &[DCZYSZ],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .with_examples(&[("https://example.com/article?fb_source=feed", "https://example.com/article")]),
+        // Links to the AMP version of a page.
+        Rule::new(vec![Any], vec![Exact("amp")])
+        .with_meta(RuleMeta {
+            id: "universal-amp",
+            description: "Flag of links to the AMP version of a page",
+            sources: &[DCZYSZ],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::SiteFunctional)
+        .with_examples(&[("https://example.com/article?amp", "https://example.com/article")]),
+        Rule::new(vec![Any], vec![Exact("gs_l")])
+        .with_meta(RuleMeta {
+            id: "universal-search-state",
+            description: "Google search box state appended to links",
+            sources: &[DCZYSZ],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::SessionState)
+        .with_examples(&[("https://example.com/?gs_l=psy-ab.3", "https://example.com/")]),
+        // Matches any tracking redirector, only urls with a `redir` or `dest` param are changed.
+        Rule::new(vec![Contains("track"), Contains("analytics")], vec![])
+        .with_meta(RuleMeta {
+            id: "tracking-redirects",
+            description: "Extracts the destination of tracking and analytics redirectors",
+            sources: &[ORIGINAL_RULES],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::Analytics)
+        .allowing_lint(LintKind::OverBroad)
+        .with_examples(&[("https://track.example.com/click?redir=https%3A%2F%2Fexample.org%2Fpage", "https://example.org/page")])
+        .with_handler_spec(HandlerSpec::ExtractFromQuery {
+            keys: vec!["redir", "dest"],
+            path_contains: vec![],
+            decode: vec![Decoding::Percent],
+        }),
+        Rule::new(vec![Domain("nytimes.com")], vec![Exact("emc")])
+        .with_meta(RuleMeta {
+            id: "nytimes-tracking",
+            description: "New York Times newsletter campaign and partner attribution",
+            sources: &[ORIGINAL_RULES],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::EmailMarketing)
+        .params_in(Category::Affiliate, vec![Exact("partner")])
+        .with_examples(&[
+            ("https://www.nytimes.com/2023/01/01/world/story.html?emc=edit_nn_20230101", "https://www.nytimes.com/2023/01/01/world/story.html"),
+            ("https://www.nytimes.com/2023/01/01/world/story.html?partner=rss", "https://www.nytimes.com/2023/01/01/world/story.html"),
+        ]),
+    ], search::rules(), ecommerce::rules(), social::rules()].into_iter().flatten().collect())
+        .with_version(env!("CARGO_PKG_VERSION"));
+
+    static ref ANALYTICS_PARAMS: Vec<M> = vec![
         Exact("_openstat"),
-        Exact("action_object_map"),
-        Exact("action_ref_map"),
-        Exact("action_type_map"),
-        Exact("adgroupid"),
-        Exact("amp"),
-        Exact("campaignid"),
         Exact("CNDID"),
-        Exact("fb_action_ids"),
-        Exact("fb_action_types"),
-        Exact("fb_ref"),
-        Exact("fb_source"),
-        Exact("fbclid"),
-        Exact("feeditemid"),
         Exact("ga_campaign"),
         Exact("ga_content"),
         Exact("ga_medium"),
         Exact("ga_place"),
         Exact("ga_source"),
         Exact("ga_term"),
-        Exact("gclid"),
-        Exact("gs_l"),
         Exact("hmb_campaign"),
         Exact("hmb_medium"),
         Exact("hmb_source"),
-        Exact("mbid"),
-        Exact("mc_cid"),
-        Exact("mc_eid"),
-        Exact("mkt_tok"),
         Exact("referrer"),
-        Exact("spJobID"),
-        Exact("spMailingID"),
-        Exact("spReportId"),
-        Exact("spUserID"),
         Exact("wt_mc_o"),
         Exact("WT.mc_ev"),
         Exact("WT.mc_id"),
         Exact("WT.srch"),
-        Exact("yclid"),
         Exact("ref"),
 
         StartsWith("pd_rd"),
@@ -203,49 +164,52 @@ lazy_static::lazy_static! {
         // Matomo: https://matomo.org/docs/tracking-campaigns/
         StartsWith("mtm_"),
         StartsWith("matomo_"),
-        // Hubspot: https://knowledge.hubspot.com/ads/ad-tracking-in-hubspot
-        StartsWith("hsa_"),
         //Piwik
         StartsWith("pk_"),
-        //Listrak
-        StartsWith("trk_"),
+        // Google Analytics cross domain linker
+        Exact("_ga"),
+    ];
+
+    static ref CLICK_ID_PARAMS: Vec<M> = vec![
+        Exact("adgroupid"),
+        Exact("campaignid"),
+        Exact("fbclid"),
+        Exact("feeditemid"),
+        Exact("yclid"),
+
+        // Hubspot: https://knowledge.hubspot.com/ads/ad-tracking-in-hubspot
+        StartsWith("hsa_"),
         // Microsoft Advertising: https://help.ads.microsoft.com/apex/index/3/en/60000
         Exact("msclkid"),
         // Google advertising:
-        Exact("_ga"),
         Exact("gclid"),
         Exact("gclsrc"),
+    ];
 
+    static ref EMAIL_MARKETING_PARAMS: Vec<M> = vec![
+        Exact("_hsenc"),
+        Exact("mbid"),
+        Exact("mc_cid"),
+        Exact("mc_eid"),
+        Exact("mkt_tok"),
+        Exact("spJobID"),
+        Exact("spMailingID"),
+        Exact("spReportId"),
+        Exact("spUserID"),
 
+        //Listrak
+        StartsWith("trk_"),
     ];
 
-}
-
-/// Given a `url` extract a valid link from the query string `query`.
-///
-/// Optionally specify list of path components that much mach
-/// before extracting the links.
-fn extract_link_from_query_string(
-    url: Url,
-    queries: Vec<&'static str>,
-    path_match: Option<Vec<&'static str>>,
-) -> Url {
-    if let Some(path_match) = path_match {
-        if !path_match.iter().all(|p| url.path().contains(p)) {
-            return url;
-        }
-    }
-    for query in queries {
-        for (_, possible_url) in url.query_pairs().filter(|(k, _)| k.eq(query)) {
-            if let Ok(found_url) = urlencoding::decode(&possible_url)
-                .map_err(anyhow::Error::from)
-                .and_then(|decoded| Url::parse(&decoded).map_err(anyhow::Error::from))
-            // must be valid url,
-            {
-                return found_url;
-            }
-        }
-    }
+    static ref SOCIAL_SHARE_PARAMS: Vec<M> = vec![
+        Exact("__twitter_impression"),
+        Exact("action_object_map"),
+        Exact("action_ref_map"),
+        Exact("action_type_map"),
+        Exact("fb_action_ids"),
+        Exact("fb_action_types"),
+        Exact("fb_ref"),
+        Exact("fb_source"),
+    ];
 
-    url
 }
@@ -0,0 +1,256 @@
+//! E-commerce rules
+//!
+//! Removes affiliate, recommendation and search attribution parameters
+//! while keeping everything that identifies the product (path, `id`,
+//! `variant`, `var`, `variation*` etc.).
+//!
+//! Sources:
+//! * [`https://github.com/ClearURLs/Rules/blob/master/data.min.json`]
+//! * [`https://portals.aliexpress.com/help/help_center_API.html`]
+//! * [`https://shopify.dev/docs/storefronts/themes/product-merchandising/recommendations`]
+use crate::{Category, Decoding, HandlerSpec, LintKind, Rule, RuleMeta, M::*};
+
+use super::services;
+
+pub(super) fn rules() -> Vec<Rule> {
+    vec![
+        // Own rule so the redirector is unwrapped whatever categories are removed.
+        Rule::new(services::AMAZON.hosts(), vec![])
+        .with_meta(RuleMeta {
+            id: "amazon-redirect",
+            description: "Extracts the destination of Amazon's internal redirector",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .allowing_lint(LintKind::OverBroad)
+        .with_examples(&[("https://www.amazon.com/gp/r.html?U=https%3A%2F%2Fwww.amazon.com%2Fdp%2FB08N5WRWNW", "https://www.amazon.com/dp/B08N5WRWNW")])
+        .with_handler_spec(HandlerSpec::ExtractFromQuery {
+            keys: vec!["U"],
+            path_contains: vec!["gp/r.html"],
+            decode: vec![Decoding::Percent],
+        }),
+        Rule::new(services::AMAZON.hosts(), vec![Exact("orig"), Exact("refRID")])
+        .with_meta(RuleMeta {
+            id: "amazon-tracking",
+            description: "Amazon internal referral paths, Associates tags, search positions and the product option that was shown",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::Analytics)
+        .params_in(
+            Category::Affiliate,
+            vec![
+                Exact("ascsubtag"),
+                Exact("creative"),
+                Exact("creativeASIN"),
+                Exact("linkCode"),
+                Exact("linkId"),
+                Exact("tag"),
+            ],
+        )
+        .params_in(
+            Category::SessionState,
+            vec![
+                Exact("crid"),
+                Exact("dchild"),
+                Exact("qid"),
+                Exact("sprefix"),
+                Exact("sr"),
+            ],
+        )
+        // `psc` selects the product option that was shown when the link was copied.
+        .params_in(Category::SiteFunctional, vec![Exact("_encoding"), Exact("ie"), Exact("psc")])
+        .order_insensitive()
+        .allowing_lint(LintKind::OverBroad)
+        .with_examples(&[
+            ("https://www.amazon.com/dp/B08N5WRWNW?orig=1&refRID=ABC", "https://www.amazon.com/dp/B08N5WRWNW"),
+            ("https://www.amazon.com/dp/B08N5WRWNW?tag=blog-20&linkCode=ll1", "https://www.amazon.com/dp/B08N5WRWNW"),
+            ("https://www.amazon.co.uk/dp/B08N5WRWNW?crid=2M&qid=1672531200&sr=8-1", "https://www.amazon.co.uk/dp/B08N5WRWNW"),
+            ("https://www.amazon.com/dp/B08N5WRWNW?th=1&psc=1", "https://www.amazon.com/dp/B08N5WRWNW?th=1"),
+        ]),
+        Rule::new(
+            services::EBAY.hosts(),
+            vec![
+                Exact("amdata"),
+                Exact("campid"),
+                Exact("customid"),
+                Exact("mkcid"),
+                Exact("mkevt"),
+                Exact("mkrid"),
+                Exact("toolid"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "ebay-tracking",
+            description: "eBay Partner Network ids and page and recommendation tracking",
+            sources: &["https://partnerhelp.ebay.com/helpcenter/s/article/What-are-the-parameters-of-an-EPN-link#tracking-link-format", super::CLEAR_URLS],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::Affiliate)
+        .params_in(
+            Category::Analytics,
+            vec![
+                Exact("_trkparms"),
+                Exact("_trksid"),
+                Exact("itmmeta"),
+                Exact("ssspo"),
+                Exact("sssrc"),
+                Exact("ssuid"),
+            ],
+        )
+        .allowing_lint(LintKind::OverBroad)
+        .with_examples(&[
+            ("https://www.ebay.com/itm/1234567890?mkcid=1&mkrid=711-53200-19255-0&campid=5338", "https://www.ebay.com/itm/1234567890"),
+            ("https://www.ebay.com/itm/1234567890?_trkparms=ispr%3D1&_trksid=p2047675", "https://www.ebay.com/itm/1234567890"),
+        ]),
+        // `spm`/`scm` are Alibaba's "super position model" page position trackers,
+        // shared by every Alibaba group storefront, each of them with a domain per country.
+        Rule::new(
+            vec![
+                Contains("aliexpress"),
+                Contains("alibaba"),
+                Contains("taobao"),
+                Contains("tmall"),
+            ],
+            vec![
+                Exact("algo_exp_id"),
+                Exact("algo_pvid"),
+                Exact("ali_refid"),
+                Exact("ali_trackid"),
+                Exact("btsid"),
+                Exact("pdp_ext_f"),
+                Exact("pdp_npi"),
+                Exact("pvid"),
+                Exact("scm"),
+                Exact("sk"),
+                Exact("spm"),
+                Exact("terminal_id"),
+                Exact("utparam"),
+                Exact("ws_ab_test"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "alibaba-tracking",
+            description: "Alibaba group page position and recommendation trackers, AliExpress Portals affiliate ids and country storefront redirection",
+            sources: &[super::CLEAR_URLS, "https://portals.aliexpress.com/help/help_center_API.html"],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::Analytics)
+        .params_in(
+            Category::Affiliate,
+            vec![
+                Exact("aff_fcid"),
+                Exact("aff_fsk"),
+                Exact("aff_platform"),
+                Exact("aff_trace_key"),
+                Exact("afSmartRedirect"),
+            ],
+        )
+        // Redirects to the storefront of the visitor's country.
+        .params_in(Category::SiteFunctional, vec![Exact("gatewayAdapt")])
+        .allowing_lint(LintKind::OverBroad)
+        .with_examples(&[
+            ("https://www.aliexpress.com/item/1005001.html?spm=a2g0o.detail&algo_pvid=abc", "https://www.aliexpress.com/item/1005001.html"),
+            ("https://www.aliexpress.com/item/1005001.html?aff_fcid=abc&aff_platform=portals-tool", "https://www.aliexpress.com/item/1005001.html"),
+            ("https://www.aliexpress.com/item/1005001.html?gatewayAdapt=glo2usa", "https://www.aliexpress.com/item/1005001.html"),
+        ]),
+        Rule::new(
+            vec![Domain("etsy.com")],
+            vec![
+                Exact("click_key"),
+                Exact("click_sum"),
+                Exact("frs"),
+                Exact("organic_search_click"),
+                Exact("plkey"),
+                Exact("pro"),
+                Exact("sr_prefetch"),
+                Exact("sts"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "etsy-tracking",
+            description: "Etsy click, listing, referral and search tracking",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::Analytics)
+        .params_in(
+            Category::SessionState,
+            vec![
+                Exact("ga_order"),
+                Exact("ga_search_query"),
+                Exact("ga_search_type"),
+                Exact("ga_view_type"),
+            ],
+        )
+        .with_examples(&[
+            ("https://www.etsy.com/listing/123/mug?click_key=abc&pro=1", "https://www.etsy.com/listing/123/mug"),
+            ("https://www.etsy.com/listing/123/mug?ga_search_query=mug&ga_order=most_relevant", "https://www.etsy.com/listing/123/mug"),
+        ]),
+        Rule::new(
+            vec![Contains("walmart.")],
+            vec![
+                Exact("athancid"),
+                Exact("athbdg"),
+                Exact("athcgid"),
+                Exact("athcpid"),
+                Exact("athguid"),
+                Exact("athieid"),
+                Exact("athmtid"),
+                Exact("athpgid"),
+                Exact("athstid"),
+                Exact("athtvid"),
+                Exact("athwpid"),
+                Exact("athznid"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "walmart-tracking",
+            description: "Walmart recommendation tracking and partner ids",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::Analytics)
+        .params_in(Category::Affiliate, vec![Exact("sourceid"), Exact("veh"), Exact("wmlspartner")])
+        .with_examples(&[
+            ("https://www.walmart.com/ip/123?athcpid=123&athpgid=AthenaItempage", "https://www.walmart.com/ip/123"),
+            ("https://www.walmart.com/ip/123?wmlspartner=abc&veh=aff&sourceid=imp_1", "https://www.walmart.com/ip/123"),
+        ]),
+        // Only `myshopify.com` hosts are matched: storefronts on their own domains can't be
+        // told apart from other sites by their url, and a `/products/` path is not specific
+        // enough. `variant` selects the product option and must be kept.
+        Rule::new(
+            vec![Domain("myshopify.com")],
+            vec![
+                Exact("pr_prod_strat"),
+                Exact("pr_rec_id"),
+                Exact("pr_rec_pid"),
+                Exact("pr_ref_pid"),
+                Exact("pr_seq"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "shopify-tracking",
+            description: "Shopify product recommendation and search result tracking",
+            sources: &["https://shopify.dev/docs/storefronts/themes/product-merchandising/recommendations", super::CLEAR_URLS],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::Analytics)
+        // Position of the product in the search results it was found in.
+        .params_in(
+            Category::SessionState,
+            vec![Exact("_pos"), Exact("_psq"), Exact("_sid"), Exact("_ss")],
+        )
+        .with_examples(&[
+            ("https://shop.myshopify.com/products/mug?variant=42&pr_prod_strat=copurchase&pr_rec_id=abc", "https://shop.myshopify.com/products/mug?variant=42"),
+            ("https://shop.myshopify.com/products/mug?variant=42&_pos=1&_sid=abc&_ss=r", "https://shop.myshopify.com/products/mug?variant=42"),
+        ]),
+    ]
+}
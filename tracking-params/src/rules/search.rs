@@ -0,0 +1,251 @@
+//! Search engine rules
+//!
+//! Keeps the query (`q`, `wd`, `text` etc.) and any result filters
+//! (`tbm`, `tbs`, `start`, `hl`...) while removing the session, experiment
+//! and client identifiers that are appended when a result page is shared.
+//!
+//! Sources:
+//! * [`https://github.com/ClearURLs/Rules/blob/master/data.min.json`]
+//! * [`https://www.google.com/support/enterprise/static/gsa/docs/admin/current/gsa_doc_set/xml_reference/request_format.html`]
+//! * [`https://yandex.com/dev/xml/doc/en/concepts/get-request`]
+use crate::{Category, Decoding, HandlerSpec, LintKind, Rule, RuleMeta, M::*};
+
+pub(super) fn rules() -> Vec<Rule> {
+    vec![
+        // Google has a domain per country (`google.co.uk`, `google.de`...).
+        Rule::new(
+            vec![Contains("google")],
+            vec![
+                Exact("cvid"),
+                Exact("ei"),
+                Exact("gws_rd"),
+                Exact("sei"),
+                Exact("ved"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "google-session",
+            description: "Experiment, event and session ids of Google pages",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::SessionState)
+        .allowing_lint(LintKind::OverBroad)
+        .with_examples(&[("https://www.google.com/maps/place/Paris?ved=2ahUKEwi&ei=abc", "https://www.google.com/maps/place/Paris")]),
+        Rule::new(vec![ContainsAll(vec!["google", "/url"])], vec![Exact("usg")])
+        .with_meta(RuleMeta {
+            id: "google-redirect",
+            description: "Google result click redirector, its destination is extracted",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::ClickId)
+        .with_examples(&[("https://www.google.com/url?sa=t&q=https://example.org/page&usg=AOvVaw0", "https://example.org/page")])
+        .with_handler_spec(HandlerSpec::ExtractFromQuery {
+            keys: vec!["q", "url"],
+            path_contains: vec![],
+            decode: vec![Decoding::Percent],
+        }),
+        // Result pages: `https://www.google.com/search?q=...`
+        Rule::new(
+            vec![ContainsAll(vec!["google.", "/search"])],
+            vec![
+                Exact("aqs"),
+                Exact("bih"),
+                Exact("biw"),
+                Exact("client"),
+                Exact("dpr"),
+                Exact("fbs"),
+                Exact("gs_lcp"),
+                Exact("gs_lcrp"),
+                Exact("gs_lp"),
+                Exact("gs_ssp"),
+                Exact("iflsig"),
+                Exact("ie"),
+                Exact("oe"),
+                Exact("oq"),
+                Exact("rlz"),
+                Exact("sa"),
+                Exact("sca_esv"),
+                Exact("sca_upv"),
+                Exact("sclient"),
+                Exact("source"),
+                Exact("sourceid"),
+                Exact("sxsrf"),
+                Exact("uact"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "google-search",
+            description: "Client, suggestion and viewport state of Google result pages",
+            sources: &[super::CLEAR_URLS, "https://www.google.com/support/enterprise/static/gsa/docs/admin/current/gsa_doc_set/xml_reference/request_format.html"],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::SessionState)
+        .with_examples(&[("https://www.google.com/search?q=rust&oq=rust&sourceid=chrome&ie=UTF-8", "https://www.google.com/search?q=rust")]),
+        Rule::new(
+            vec![Domain("bing.com")],
+            vec![
+                Exact("cvid"),
+                Exact("form"),
+                Exact("FORM"),
+                Exact("ghacc"),
+                Exact("ghc"),
+                Exact("ghpl"),
+                Exact("ghsh"),
+                Exact("lq"),
+                Exact("PC"),
+                Exact("pq"),
+                Exact("qpvt"),
+                Exact("qs"),
+                Exact("refig"),
+                Exact("sc"),
+                Exact("sk"),
+                Exact("sp"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "bing-search",
+            description: "Form, suggestion and conversation ids of Bing",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::SessionState)
+        .with_examples(&[("https://www.bing.com/search?q=rust&form=QBLH&sp=-1&cvid=ABC", "https://www.bing.com/search?q=rust")]),
+        Rule::new(vec![Domain("duckduckgo.com")], vec![Exact("atb"), Exact("t")])
+        .with_meta(RuleMeta {
+            id: "duckduckgo-cohort",
+            description: "DuckDuckGo install cohort",
+            sources: &["https://help.duckduckgo.com/duckduckgo-help-pages/privacy/atb/"],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::Analytics)
+        .with_examples(&[("https://duckduckgo.com/?q=rust&t=h_&atb=v1-1", "https://duckduckgo.com/?q=rust")]),
+        // Non-javascript result links: `https://duckduckgo.com/l/?uddg=...&rut=...`
+        Rule::new(vec![ContainsAll(vec!["duckduckgo.com", "/l/"])], vec![Exact("rut")])
+        .with_meta(RuleMeta {
+            id: "duckduckgo-redirect",
+            description: "DuckDuckGo non javascript result redirector, its destination is extracted",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::ClickId)
+        .with_examples(&[("https://duckduckgo.com/l/?uddg=https%3A%2F%2Fexample.org%2Fpage&rut=abc", "https://example.org/page")])
+        .with_handler_spec(HandlerSpec::ExtractFromQuery {
+            keys: vec!["uddg"],
+            path_contains: vec![],
+            decode: vec![Decoding::Percent],
+        }),
+        // `clid` identifies the partner that distributed the browser or search widget.
+        Rule::new(vec![Contains("yandex."), StartsWith("ya.ru/")], vec![Exact("clid")])
+        .with_meta(RuleMeta {
+            id: "yandex-tracking",
+            description: "Yandex distribution partner ids and search session state",
+            sources: &[super::CLEAR_URLS, "https://yandex.com/dev/xml/doc/en/concepts/get-request"],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::Affiliate)
+        .params_in(
+            Category::SessionState,
+            vec![
+                Exact("lr"),
+                Exact("msid"),
+                Exact("redircnt"),
+                Exact("search_source"),
+                Exact("src"),
+                Exact("stred"),
+                Exact("suggest_reqid"),
+                Exact("win"),
+            ],
+        )
+        .with_examples(&[
+            ("https://yandex.ru/search/?text=rust&clid=2270455", "https://yandex.ru/search/?text=rust"),
+            ("https://yandex.ru/search/?text=rust&lr=213&msid=123.456", "https://yandex.ru/search/?text=rust"),
+        ]),
+        // Click redirector, the destination is only decodable when passed as `url`
+        // (the `data` parameter is encrypted).
+        Rule::new(vec![ContainsAll(vec!["yandex.", "/clck/jsredir"])], vec![])
+        .with_meta(RuleMeta {
+            id: "yandex-redirect",
+            description: "Yandex click redirector, its destination is extracted",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::ClickId)
+        .with_examples(&[("https://yandex.ru/clck/jsredir?url=https%3A%2F%2Fexample.org%2Fpage", "https://example.org/page")])
+        .with_handler_spec(HandlerSpec::ExtractFromQuery {
+            keys: vec!["url"],
+            path_contains: vec![],
+            decode: vec![Decoding::Percent],
+        }),
+        Rule::new(
+            vec![Contains("baidu.")],
+            vec![
+                StartsWith("rsv_"),
+                Exact("bar"),
+                Exact("f"),
+                Exact("ie"),
+                Exact("inputT"),
+                Exact("issp"),
+                Exact("oq"),
+                Exact("prefixsug"),
+                Exact("rqlang"),
+                Exact("rsp"),
+                Exact("tn"),
+                Exact("usm"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "baidu-search",
+            description: "Baidu search session and suggestion state",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::SessionState)
+        .with_examples(&[("https://www.baidu.com/s?wd=rust&rsv_spt=1&ie=utf-8&tn=baiduhome_pg", "https://www.baidu.com/s?wd=rust")]),
+        // Click redirector, most of the time `url` is an opaque token
+        // which is left untouched.
+        Rule::new(vec![ContainsAll(vec!["baidu.", "/link"])], vec![Exact("eqid")])
+        .with_meta(RuleMeta {
+            id: "baidu-redirect",
+            description: "Baidu click redirector, its destination is extracted",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::ClickId)
+        .with_examples(&[("https://www.baidu.com/link?url=https%3A%2F%2Fexample.org%2Fpage&eqid=abc", "https://example.org/page")])
+        .with_handler_spec(HandlerSpec::ExtractFromQuery {
+            keys: vec!["url"],
+            path_contains: vec![],
+            decode: vec![Decoding::Percent],
+        }),
+        Rule::new(
+            vec![Domain("ecosia.org")],
+            vec![
+                Exact("addon"),
+                Exact("addonversion"),
+                Exact("tt"),
+                Exact("tts"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "ecosia-addon",
+            description: "Ecosia browser extension ids",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::Analytics)
+        .with_examples(&[("https://www.ecosia.org/search?q=rust&addon=firefox&addonversion=4.1.0", "https://www.ecosia.org/search?q=rust")]),
+    ]
+}
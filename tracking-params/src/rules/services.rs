@@ -0,0 +1,135 @@
+//! Services reachable from more than one host name
+//!
+//! Most services use short domains for sharing (`youtu.be`, `redd.it`, `pin.it` ...)
+//! or have been renamed (`twitter.com` to `x.com`), rules for these services
+//! must match all of them.
+use url::Url;
+
+use crate::{Service, M::*};
+
+/// `Contains` as the stores have a domain per country (`amazon.co.uk`, `amazon.de`...).
+pub(crate) static AMAZON: Service = Service {
+    hosts: &[
+        Contains("amazon"),
+        Domain("a.co"),
+        Domain("amzn.asia"),
+        Domain("amzn.com"),
+        Domain("amzn.eu"),
+        Domain("amzn.to"),
+    ],
+    expand: None,
+};
+
+/// `Contains` as the stores have a domain per country (`ebay.co.uk`, `ebay.de`...).
+pub(crate) static EBAY: Service = Service {
+    hosts: &[Contains("ebay"), Domain("ebay.us")],
+    expand: None,
+};
+
+pub(crate) static FACEBOOK: Service = Service {
+    hosts: &[
+        Contains("facebook."),
+        Domain("fb.com"),
+        Domain("fb.me"),
+        Domain("fb.watch"),
+    ],
+    expand: None,
+};
+
+pub(crate) static INSTAGRAM: Service = Service {
+    hosts: &[Domain("instagram.com"), Domain("instagr.am")],
+    expand: Some(expand_instagr_am),
+};
+
+pub(crate) static LINKEDIN: Service = Service {
+    hosts: &[Domain("linkedin.com"), Domain("lnkd.in")],
+    expand: None,
+};
+
+pub(crate) static PINTEREST: Service = Service {
+    hosts: &[Contains("pinterest."), Domain("pin.it")],
+    expand: None,
+};
+
+pub(crate) static REDDIT: Service = Service {
+    hosts: &[Domain("reddit.com"), Domain("redd.it")],
+    expand: Some(expand_redd_it),
+};
+
+pub(crate) static SPOTIFY: Service = Service {
+    hosts: &[
+        Domain("spotify.com"),
+        Domain("spotify.link"),
+        Domain("spoti.fi"),
+    ],
+    expand: None,
+};
+
+pub(crate) static TWITTER: Service = Service {
+    hosts: &[Domain("twitter.com"), Domain("x.com")],
+    expand: None,
+};
+
+pub(crate) static YOUTUBE: Service = Service {
+    hosts: &[
+        Domain("youtube.com"),
+        Domain("youtube-nocookie.com"),
+        Domain("youtu.be"),
+    ],
+    expand: Some(expand_youtu_be),
+};
+
+/// `https://youtu.be/ID?t=5` -> `https://www.youtube.com/watch?v=ID&t=5`
+fn expand_youtu_be(url: &Url) -> Option<Url> {
+    if url.host_str()? != "youtu.be" {
+        return None;
+    }
+    let id = first_path_segment(url)?;
+
+    let mut expanded = Url::parse("https://www.youtube.com/watch").ok()?;
+    match url.query() {
+        Some(query) if !query.is_empty() => {
+            expanded.set_query(Some(&format!("v={}&{}", id, query)));
+        }
+        _ => expanded.set_query(Some(&format!("v={}", id))),
+    }
+    expanded.set_fragment(url.fragment());
+
+    Some(expanded)
+}
+
+/// `https://redd.it/ID` -> `https://www.reddit.com/comments/ID`
+fn expand_redd_it(url: &Url) -> Option<Url> {
+    if url.host_str()? != "redd.it" {
+        return None;
+    }
+
+    replace_origin(url, "https://www.reddit.com/comments/")
+}
+
+/// `https://instagr.am/p/ID` -> `https://www.instagram.com/p/ID`
+fn expand_instagr_am(url: &Url) -> Option<Url> {
+    if !matches!(url.host_str()?, "instagr.am" | "www.instagr.am") {
+        return None;
+    }
+
+    replace_origin(url, "https://www.instagram.com/")
+}
+
+fn first_path_segment(url: &Url) -> Option<&str> {
+    url.path_segments()?.next().filter(|s| !s.is_empty())
+}
+
+/// Joins the path, query and fragment of `url` to `base`.
+fn replace_origin(url: &Url, base: &str) -> Option<Url> {
+    first_path_segment(url)?;
+
+    let mut expanded = Url::parse(base)
+        .ok()?
+        .join(url.path().trim_start_matches('/'))
+        .ok()?;
+    expanded.set_query(url.query());
+    expanded.set_fragment(url.fragment());
+
+    Some(expanded)
+}
@@ -0,0 +1,277 @@
+//! Social network and video platform rules
+//!
+//! Mostly covers the parameters appended by the "share" / "copy link" buttons
+//! in the apps and web clients of each platform.
+//!
+//! Sources:
+//! * [`https://github.com/ClearURLs/Rules/blob/master/data.min.json`]
+//! * [`https://github.com/AdguardTeam/AdguardFilters/blob/master/TrackParamFilter/sections/specific.txt`]
+use crate::{Category, Decoding, HandlerSpec, Rule, RuleMeta, M::*};
+
+use super::services;
+
+pub(super) fn rules() -> Vec<Rule> {
+    vec![
+        Rule::new(vec![ContainsAll(vec!["linkedin.com", "messaging", "thread"])], vec![AllBut("_")])
+        .with_meta(RuleMeta {
+            id: "linkedin-messaging",
+            description: "LinkedIn messaging thread links, only `_` is kept",
+            sources: &[super::ORIGINAL_RULES],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::SessionState)
+        .with_examples(&[("https://www.linkedin.com/messaging/thread/2-abc?_=1&lipi=abc", "https://www.linkedin.com/messaging/thread/2-abc?_=1")]),
+        Rule::new(
+            services::LINKEDIN.hosts(),
+            vec![
+                Exact("eid"),
+                Exact("lici"),
+                Exact("lipi"),
+                Exact("originalSubdomain"),
+                Exact("original_referer"),
+                Exact("refId"),
+                Exact("trackingId"),
+                Exact("trk"),
+                Exact("trkInfo"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "linkedin-share",
+            description: "LinkedIn share, feed and referral tracking and notification email tokens",
+            sources: &[super::CLEAR_URLS, super::ADGUARD],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        // Links of the notification emails.
+        .params_in(
+            Category::EmailMarketing,
+            vec![Exact("midSig"), Exact("midToken"), Exact("trkEmail")],
+        )
+        .with_examples(&[
+            ("https://www.linkedin.com/posts/jane_rust-activity-1?trk=public_post&lipi=abc", "https://www.linkedin.com/posts/jane_rust-activity-1"),
+            ("https://www.linkedin.com/comm/jobs/view/123?midToken=AQ&trkEmail=eml", "https://www.linkedin.com/comm/jobs/view/123"),
+        ]),
+        Rule::new(services::INSTAGRAM.hosts(), vec![Exact("igsh"), Exact("igshid")])
+        .with_meta(RuleMeta {
+            id: "instagram-share",
+            description: "Instagram share sheet id",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .with_examples(&[("https://www.instagram.com/p/Cabc123/?igsh=MWQ1ZGUxMzBkMA==", "https://www.instagram.com/p/Cabc123/")]),
+        // Meta's internal click tracking, `__cft__[0]` and `__xts__[0]` are sent as arrays.
+        Rule::new(
+            services::FACEBOOK.hosts(),
+            vec![
+                StartsWith("__cft__"),
+                StartsWith("__tn__"),
+                StartsWith("__xts__"),
+                Exact("acontext"),
+                Exact("comment_tracking"),
+                Exact("eid"),
+                Exact("fref"),
+                Exact("hc_location"),
+                Exact("hc_ref"),
+                Exact("mibextid"),
+                Exact("notif_id"),
+                Exact("notif_t"),
+                Exact("paipv"),
+                Exact("rdid"),
+                Exact("refid"),
+                Exact("sfnsn"),
+                Exact("share_url"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "facebook-share",
+            description: "Facebook click, notification and share tracking",
+            sources: &[super::CLEAR_URLS, super::ADGUARD],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .with_examples(&[("https://www.facebook.com/story.php?story_fbid=1&id=2&mibextid=Nif5oz", "https://www.facebook.com/story.php?story_fbid=1&id=2")]),
+        Rule::new(
+            vec![Domain("threads.net"), Domain("threads.com")],
+            vec![Exact("slof"), Exact("xmt")],
+        )
+        .with_meta(RuleMeta {
+            id: "threads-share",
+            description: "Threads share ids",
+            sources: &[super::ADGUARD],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .with_examples(&[("https://www.threads.net/@jane/post/C1?xmt=AQ&slof=1", "https://www.threads.net/@jane/post/C1")]),
+        Rule::new(
+            services::REDDIT.hosts(),
+            vec![
+                Exact("context"),
+                Exact("correlation_id"),
+                Exact("post_fullname"),
+                Exact("post_index"),
+                Exact("rdt"),
+                Exact("ref"),
+                Exact("ref_campaign"),
+                Exact("ref_source"),
+                Exact("share_id"),
+                Exact("st"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "reddit-share",
+            description: "Reddit share and referral ids",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .with_examples(&[("https://www.reddit.com/r/rust/comments/abc/title/?share_id=xyz&rdt=123", "https://www.reddit.com/r/rust/comments/abc/title/")]),
+        Rule::new(
+            services::TWITTER.hosts(),
+            vec![
+                Exact("context"),
+                Exact("vertical"),
+                Exact("src"),
+                Exact("s"),
+                Exact("t"),
+                Exact("ref_src"),
+                Exact("ref_url"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "twitter-share",
+            description: "Twitter / X share and referral ids",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .with_examples(&[
+            ("https://twitter.com/elonmusk/status/1608273870901096454?ref_src=twsrc%5EdUmBgUY", "https://twitter.com/elonmusk/status/1608273870901096454"),
+            ("https://x.com/jane/status/1?s=20&t=abc", "https://x.com/jane/status/1"),
+            ]),
+        Rule::new(
+            vec![Domain("tiktok.com")],
+            vec![
+                Exact("_d"),
+                Exact("_r"),
+                Exact("_t"),
+                Exact("checksum"),
+                Exact("is_copy_url"),
+                Exact("is_from_webapp"),
+                Exact("preview_pb"),
+                Exact("sec_user_id"),
+                Exact("sender_device"),
+                Exact("share_app_id"),
+                Exact("share_item_id"),
+                Exact("share_link_id"),
+                Exact("social_sharing"),
+                Exact("source"),
+                Exact("timestamp"),
+                Exact("tt_from"),
+                Exact("u_code"),
+                Exact("user_id"),
+                Exact("web_id"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "tiktok-share",
+            description: "TikTok share, device and user ids",
+            sources: &[super::CLEAR_URLS, super::ADGUARD],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .with_examples(&[("https://www.tiktok.com/@jane/video/123?is_from_webapp=1&sender_device=pc&web_id=456", "https://www.tiktok.com/@jane/video/123")]),
+        Rule::new(
+            services::YOUTUBE.hosts(),
+            vec![
+                Contains("ab_channel"),
+                Contains("attr_tag"),
+                Contains("feature"),
+                Exact("embeds_referring_euri"),
+                Exact("embeds_referring_origin"),
+                Exact("kw"),
+                Exact("pp"),
+                Exact("si"),
+                Exact("source_ve_path"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "youtube-share",
+            description: "YouTube share ids and feature attribution, its redirector destination is extracted",
+            sources: &[super::CLEAR_URLS],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .preserving(vec![Exact("v"), Exact("list"), Exact("t"), Exact("index")])
+        .order_insensitive()
+        .with_examples(&[
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42&si=abc&feature=share", "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42"),
+            ("https://www.youtube.com/redirect?event=video_description&q=https%3A%2F%2Fexample.org%2Fpage", "https://example.org/page"),
+            ])
+        .with_handler_spec(HandlerSpec::ExtractFromQuery {
+            keys: vec!["q"],
+            path_contains: vec!["redirect"],
+            decode: vec![Decoding::Percent],
+        }),
+        Rule::new(vec![Domain("twitch.tv")], vec![StartsWith("tt_"), Exact("sr")])
+        .with_meta(RuleMeta {
+            id: "twitch-share",
+            description: "Twitch share and recommendation tracking",
+            sources: &[super::ADGUARD],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .with_examples(&[("https://www.twitch.tv/jane?sr=a&tt_medium=redt", "https://www.twitch.tv/jane")]),
+        // `h` is the privacy hash of unlisted videos and must be kept.
+        Rule::new(vec![Domain("vimeo.com")], vec![Exact("fe"), Exact("fl"), Exact("share")])
+        .with_meta(RuleMeta {
+            id: "vimeo-share",
+            description: "Vimeo share attribution",
+            sources: &[super::ADGUARD],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .preserving(vec![Exact("h")])
+        .with_examples(&[("https://vimeo.com/123/abcdef?h=abcdef&share=copy", "https://vimeo.com/123/abcdef?h=abcdef")]),
+        Rule::new(
+            services::PINTEREST.hosts(),
+            vec![
+                Exact("epik"),
+                Exact("invite_code"),
+                Exact("mweb_unauth_id"),
+                Exact("sender"),
+                Exact("sfo"),
+            ],
+        )
+        .with_meta(RuleMeta {
+            id: "pinterest-share",
+            description: "Pinterest share and invite ids",
+            sources: &[super::CLEAR_URLS, super::ADGUARD],
+            added_in: "0.2.0",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .with_examples(&[("https://www.pinterest.com/pin/123/?invite_code=abc&sender=456", "https://www.pinterest.com/pin/123/")]),
+        Rule::new(services::SPOTIFY.hosts(), vec![Exact("dl_branch"), Exact("nd"), Exact("si")])
+        .with_meta(RuleMeta {
+            id: "spotify-share",
+            description: "Spotify share ids",
+            sources: &["https://community.spotify.com/t5/Desktop-Windows/si-Parameter-in-Spotify-URL-s/td-p/4538290"],
+            added_in: "0.1.6",
+            deprecated: None,
+        })
+        .with_category(Category::SocialShare)
+        .with_examples(&[("https://open.spotify.com/track/4cOdK2wGLETKBW3PvgPWqT?si=abc123", "https://open.spotify.com/track/4cOdK2wGLETKBW3PvgPWqT")]),
+    ]
+}
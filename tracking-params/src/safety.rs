@@ -0,0 +1,206 @@
+use std::fmt;
+
+use unicode_security::{skeleton, RestrictionLevel, RestrictionLevelDetection};
+use url::{Host, Url};
+
+/// More labels than this in a domain (eg. `paypal.com.login.secure.evil.example`)
+/// is a common way to hide the real domain from people reading the url.
+const MAX_DOMAIN_LABELS: usize = 5;
+
+/// How risky it is to open a url, see [`crate::Cleaned::safety`].
+///
+/// ```rust
+/// let cleaned = tracking_params::clean_str_raw("https://paypal.com@192.168.0.1:8443/login")?;
+/// let safety = cleaned.safety();
+///
+/// assert!(safety.userinfo && safety.ip_literal && safety.non_standard_port);
+/// assert!(!safety.is_safe());
+///
+/// let cleaned = tracking_params::clean_str_raw("https://xn--pple-43d.com/")?;
+/// let idn = cleaned.safety().idn.unwrap();
+///
+/// assert_eq!(idn.unicode, "аpple.com");
+/// assert_eq!(idn.skeleton, "apple.com");
+/// assert!(idn.confusable && idn.mixed_script);
+/// # Ok::<_, url::ParseError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Safety {
+    pub scheme: SchemeRisk,
+    /// `https://paypal.com@evil.example/`, the part before `@` looks like the host but isn't.
+    pub userinfo: bool,
+    /// `http://192.168.0.1/`, the host is an IP address instead of a domain.
+    pub ip_literal: bool,
+    /// `https://example.com:8443/`
+    pub non_standard_port: bool,
+    /// The domain has more than 5 labels.
+    pub excessive_subdomains: bool,
+    /// Set when the domain is an internationalized domain name (eg. `xn--pple-43d.com`).
+    pub idn: Option<Idn>,
+}
+
+/// An internationalized domain name and the [Unicode TR39](https://www.unicode.org/reports/tr39/)
+/// checks used to detect that it imitates another domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Idn {
+    /// `аpple.com`
+    pub unicode: String,
+    /// `xn--pple-43d.com`
+    pub punycode: String,
+    /// The domain with its non ASCII characters replaced by the characters they can be
+    /// confused with (eg. `apple.com`).
+    pub skeleton: String,
+    /// The skeleton is an ASCII domain, so the domain can pass for it.
+    pub confusable: bool,
+    /// A label mixes characters of different scripts (eg. Latin and Cyrillic). Latin mixed
+    /// with Han, Kana or Hangul (eg. `東京tokyo.jp`) is common and allowed, as in the
+    /// [highly restrictive](https://www.unicode.org/reports/tr39/#highly_restrictive) level.
+    pub mixed_script: bool,
+}
+
+impl Idn {
+    fn detect(domain: &str) -> Option<Self> {
+        if !domain.split('.').any(|label| label.starts_with("xn--")) {
+            return None;
+        }
+
+        let (unicode, result) = idna::domain_to_unicode(domain);
+        result.ok()?;
+        let skeleton = unicode
+            .chars()
+            .flat_map(|c| match c.is_ascii() {
+                true => vec![c],
+                false => skeleton(c.encode_utf8(&mut [0; 4])).collect(),
+            })
+            .collect::<String>();
+
+        Some(Self {
+            confusable: skeleton.is_ascii() && skeleton != unicode,
+            mixed_script: unicode
+                .split('.')
+                .any(|label| !label.check_restriction_level(RestrictionLevel::HighlyRestrictive)),
+            punycode: domain.to_string(),
+            unicode,
+            skeleton,
+        })
+    }
+}
+
+/// Risk of the scheme of an url.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemeRisk {
+    /// `http` and `https`.
+    Web,
+    /// Schemes that open another app (eg. `mailto`, `tel`, `market`).
+    App,
+    /// Schemes that run code or show content that pretends to come from the current page
+    /// (eg. `javascript`, `data`), they must never be opened.
+    Dangerous,
+}
+
+impl Safety {
+    pub(crate) fn classify(url: &Url) -> Self {
+        let scheme = match url.scheme() {
+            "http" | "https" => SchemeRisk::Web,
+            "javascript" | "vbscript" | "data" | "blob" | "file" | "filesystem" => {
+                SchemeRisk::Dangerous
+            }
+            _ => SchemeRisk::App,
+        };
+
+        Self {
+            scheme,
+            userinfo: !url.username().is_empty() || url.password().is_some(),
+            ip_literal: matches!(url.host(), Some(Host::Ipv4(_) | Host::Ipv6(_))),
+            non_standard_port: url.port().is_some(),
+            excessive_subdomains: matches!(
+                url.host(),
+                Some(Host::Domain(domain)) if domain.split('.').count() > MAX_DOMAIN_LABELS
+            ),
+            idn: match url.host() {
+                Some(Host::Domain(domain)) => Idn::detect(domain),
+                _ => None,
+            },
+        }
+    }
+
+    /// Whether none of the risks were found, `App` schemes are not considered a risk.
+    pub fn is_safe(&self) -> bool {
+        self.warnings().is_empty()
+    }
+
+    /// The risks found, in a stable order.
+    pub fn warnings(&self) -> Vec<Warning> {
+        let idn_warnings = self.idn.iter().flat_map(|idn| {
+            [
+                idn.confusable.then(|| Warning::Confusable {
+                    skeleton: idn.skeleton.clone(),
+                }),
+                idn.mixed_script.then_some(Warning::MixedScript),
+            ]
+        });
+
+        [
+            (
+                self.scheme == SchemeRisk::Dangerous,
+                Warning::DangerousScheme,
+            ),
+            (self.userinfo, Warning::Userinfo),
+            (self.ip_literal, Warning::IpLiteral),
+            (self.non_standard_port, Warning::NonStandardPort),
+            (self.excessive_subdomains, Warning::ExcessiveSubdomains),
+        ]
+        .into_iter()
+        .filter(|(risk, _)| *risk)
+        .map(|(_, warning)| warning)
+        .chain(idn_warnings.flatten())
+        .collect()
+    }
+}
+
+/// A risk found by [`Safety::warnings`], its `Display` is a short explanation for people
+/// about to open the url.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// See [`SchemeRisk::Dangerous`].
+    DangerousScheme,
+    /// See [`Safety::userinfo`].
+    Userinfo,
+    /// See [`Safety::ip_literal`].
+    IpLiteral,
+    /// See [`Safety::non_standard_port`].
+    NonStandardPort,
+    /// See [`Safety::excessive_subdomains`].
+    ExcessiveSubdomains,
+    /// See [`Idn::confusable`], with the domain it imitates.
+    Confusable { skeleton: String },
+    /// See [`Idn::mixed_script`].
+    MixedScript,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::DangerousScheme => {
+                f.write_str("The link runs code or shows content that is not from a website")
+            }
+            Warning::Userinfo => {
+                f.write_str("The link contains a user name, the website is the part after `@`")
+            }
+            Warning::IpLiteral => {
+                f.write_str("The link points to an IP address instead of a domain name")
+            }
+            Warning::NonStandardPort => f.write_str("The link uses a non standard port"),
+            Warning::ExcessiveSubdomains => {
+                f.write_str("The domain name has an unusual number of subdomains")
+            }
+            Warning::Confusable { skeleton } => {
+                write!(f, "The domain name imitates `{skeleton}`")
+            }
+            Warning::MixedScript => {
+                f.write_str("The domain name mixes characters of different scripts")
+            }
+        }
+    }
+}
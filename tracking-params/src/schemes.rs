@@ -0,0 +1,128 @@
+//! Cleaning of urls that are not web pages but can carry tracking params
+//! or web page urls that do.
+use std::borrow::Cow;
+
+use url::Url;
+
+use crate::{
+    lenient::trim_punctuation,
+    nested::{clean_value, escape_like},
+    query_key, Options, RuleSet,
+};
+
+/// Cleans the web page an Android intent falls back to when the app is not installed, eg.
+/// `intent://scan/#Intent;scheme=zxing;S.browser_fallback_url=https%3A%2F%2Fzxing.org;end`.
+/// The rest of the intent is kept as is and the fallback url is escaped the same way it was.
+pub(crate) fn clean_intent(mut url: Url, set: &RuleSet, options: &Options) -> Url {
+    let Some(items) = url.fragment().and_then(|f| f.strip_prefix("Intent;")) else {
+        return url;
+    };
+
+    let mut changed = false;
+    let items = items
+        .split(';')
+        .map(|item| {
+            let cleaned = item
+                .strip_prefix("S.browser_fallback_url=")
+                .and_then(|value| clean_value(value, set, options));
+            match cleaned {
+                Some(cleaned) => {
+                    changed = true;
+                    Cow::Owned(format!("S.browser_fallback_url={cleaned}"))
+                }
+                None => Cow::Borrowed(item),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(";");
+
+    if changed {
+        url.set_fragment(Some(&format!("Intent;{items}")));
+    }
+    url
+}
+
+/// Cleans the urls found in the headers (eg. `body`) of a `mailto:` url,
+/// the headers are escaped the same way they were.
+pub(crate) fn clean_mailto(mut url: Url, set: &RuleSet, options: &Options) -> Url {
+    let Some(query) = url.query() else {
+        return url;
+    };
+
+    let mut changed = false;
+    let query = query
+        .split('&')
+        .map(|header| {
+            let cleaned = header.split_once('=').and_then(|(name, value)| {
+                let text = urlencoding::decode(value).ok()?;
+                let cleaned = clean_urls_in_text(&text, set, options)?;
+                Some(format!("{name}={}", escape_like(&cleaned, value)))
+            });
+            match cleaned {
+                Some(cleaned) => {
+                    changed = true;
+                    cleaned
+                }
+                None => header.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    if changed {
+        url.set_query(Some(&query));
+    }
+    url
+}
+
+/// `text` with its `http(s)` urls cleaned, `None` when none of them changed.
+fn clean_urls_in_text(text: &str, set: &RuleSet, options: &Options) -> Option<String> {
+    let mut cleaned = String::with_capacity(text.len());
+    let mut changed = false;
+    let mut rest = text;
+
+    while let Some(start) = [rest.find("http://"), rest.find("https://")]
+        .into_iter()
+        .flatten()
+        .min()
+    {
+        let (before, from_url) = rest.split_at(start);
+        let end = from_url
+            .find(|c: char| c.is_whitespace() || "<>\"'".contains(c))
+            .unwrap_or(from_url.len());
+        let candidate = trim_punctuation(&from_url[..end]);
+
+        cleaned.push_str(before);
+        // Compared to the parsed url so that only removed params count as a change.
+        let parsed = Url::parse(candidate).ok();
+        match parsed.map(|url| (url.to_string(), set.clean_with(url, options).to_string())) {
+            Some((parsed, url)) if parsed != url => {
+                changed = true;
+                cleaned.push_str(&url);
+            }
+            _ => cleaned.push_str(candidate),
+        }
+        rest = &from_url[candidate.len()..];
+    }
+    cleaned.push_str(rest);
+
+    changed.then_some(cleaned)
+}
+
+/// Removes the install attribution of Play Store links, eg.
+/// `market://details?id=com.example&referrer=utm_source%3Dnewsletter`
+pub(crate) fn clean_market(mut url: Url) -> Url {
+    let Some(query) = url.query() else {
+        return url;
+    };
+
+    let kept = query
+        .split('&')
+        .filter(|pair| !pair.is_empty() && query_key(pair) != "referrer")
+        .collect::<Vec<_>>()
+        .join("&");
+    if kept != query {
+        url.set_query((!kept.is_empty()).then_some(kept.as_str()));
+    }
+    url
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+use url::Url;
+
+use crate::{meta::key, RuleSet};
+
+/// An example of a rule that is not cleaned as expected, see [`RuleSet::verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExampleFailure {
+    /// The [`crate::RuleMeta::id`] of the rule, or its hosts when it doesn't have one.
+    pub rule: String,
+    pub input: &'static str,
+    pub expected: &'static str,
+    /// What `input` is cleaned to, or why it couldn't be.
+    pub actual: Result<String, url::ParseError>,
+    /// Whether the rule applies to `input`, an example of another rule tests nothing.
+    pub matched: bool,
+}
+
+impl fmt::Display for ExampleFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] `{}`", self.rule, self.input)?;
+        match &self.actual {
+            Err(err) => write!(f, " is not a valid url: {err}"),
+            Ok(_) if !self.matched => write!(f, " is not matched by the rule"),
+            Ok(actual) => write!(f, ": expected `{}`, got `{actual}`", self.expected),
+        }
+    }
+}
+
+pub(crate) fn verify(set: &RuleSet) -> Result<(), Vec<ExampleFailure>> {
+    let mut failures = vec![];
+
+    for rule in set.rules() {
+        for &(input, expected) in rule.examples {
+            let (actual, matched) = match Url::parse(input) {
+                Ok(url) => {
+                    let matched = set
+                        .matching(&url)
+                        .rules()
+                        .any(|matched| std::ptr::eq(matched, rule));
+                    (Ok(set.clean(url).to_string()), matched)
+                }
+                Err(err) => (Err(err), false),
+            };
+
+            if !matched || actual.as_deref() != Ok(expected) {
+                failures.push(ExampleFailure {
+                    rule: key(rule),
+                    input,
+                    expected,
+                    actual,
+                    matched,
+                });
+            }
+        }
+    }
+
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err(failures),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Rule, RuleMeta, M};
+
+    fn rule(id: &'static str, examples: &'static [(&'static str, &'static str)]) -> Rule {
+        Rule::new(vec![M::Domain("example.com")], vec![M::Exact("ref_id")])
+            .with_meta(RuleMeta {
+                id,
+                ..Default::default()
+            })
+            .with_examples(examples)
+    }
+
+    #[test]
+    fn verifies_examples() {
+        let set = RuleSet::new(vec![
+            rule(
+                "passing",
+                &[(
+                    "https://example.com/?ref_id=1&q=2",
+                    "https://example.com/?q=2",
+                )],
+            ),
+            rule(
+                "failing",
+                &[
+                    (
+                        "https://example.com/?ref_id=1",
+                        "https://example.com/?ref_id=1",
+                    ),
+                    ("https://other.com/", "https://other.com/"),
+                    ("example.com", "https://example.com/"),
+                ],
+            ),
+        ]);
+
+        let failures = set.verify().unwrap_err();
+
+        assert_eq!(
+            failures.iter().map(|f| f.to_string()).collect::<Vec<_>>(),
+            vec![
+                "[failing] `https://example.com/?ref_id=1`: expected `https://example.com/?ref_id=1`, got `https://example.com/`",
+                "[failing] `https://other.com/` is not matched by the rule",
+                "[failing] `example.com` is not a valid url: relative URL without a base",
+            ]
+        );
+        assert_eq!(RuleSet::new(vec![rule("none", &[])]).verify(), Ok(()));
+    }
+}
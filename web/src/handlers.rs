@@ -1,8 +1,88 @@
 use actix_web::{http::StatusCode, HttpResponse, Responder};
 use serde_json::json;
+use tracking_params::{Categories, Category, Options, Preset};
 
 const BLOCKED: [&str; 1] = ["peculatemusic.com"];
 
+lazy_static::lazy_static! {
+    static ref UNSAFE_URL_POLICY: UnsafeUrlPolicy = std::env::var("UNSAFE_URL_POLICY")
+        .ok()
+        .and_then(|policy| UnsafeUrlPolicy::parse(&policy))
+        .unwrap_or(UnsafeUrlPolicy::Interstitial);
+}
+
+/// What to do with urls that are not [`tracking_params::Safety::is_safe`],
+/// set with the `UNSAFE_URL_POLICY` environment variable.
+///
+/// Urls with a dangerous scheme (eg. `javascript:`) are always refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnsafeUrlPolicy {
+    /// `refuse`: show the warnings without a link to the url.
+    Refuse,
+    /// `interstitial` (default): show the warnings with a link to continue to the url.
+    Interstitial,
+}
+
+impl UnsafeUrlPolicy {
+    fn parse(policy: &str) -> Option<Self> {
+        match policy {
+            "refuse" => Some(Self::Refuse),
+            "interstitial" => Some(Self::Interstitial),
+            _ => None,
+        }
+    }
+}
+
+/// Responses depend on the headers read by [`requested_categories`], shared caches must
+/// not serve the url cleaned for one preset to requests for another.
+const VARY: (&str, &str) = ("vary", "x-clean-preset, x-keep-categories");
+
+/// Params read before the url to clean, eg. `/?clean_preset=safe&keep_categories=affiliate&https://...`,
+/// so links can choose the categories that browsers can't send headers for.
+const CLEAN_PARAMS: [&str; 2] = ["clean_preset", "keep_categories"];
+
+/// Splits the leading [`CLEAN_PARAMS`] from the url to clean.
+fn split_clean_params(query: &str) -> (Vec<(&str, &str)>, &str) {
+    let mut params = vec![];
+    let mut rest = query;
+    while let Some((param, after)) = rest.split_once('&') {
+        match param.split_once('=') {
+            Some((name, value)) if CLEAN_PARAMS.contains(&name) => params.push((name, value)),
+            _ => break,
+        }
+        rest = after;
+    }
+    (params, rest)
+}
+
+/// The categories of trackers to remove, from the `clean_preset` param or the `x-clean-preset`
+/// header (`safe`, `standard` or `strict`, the default) minus the comma separated categories
+/// of the `keep_categories` param or the `x-keep-categories` header (eg. `affiliate` to keep
+/// affiliate tags). Params win over headers, unknown names are ignored.
+fn requested_categories(req: &actix_web::HttpRequest, params: &[(&str, &str)]) -> Categories {
+    let value = |param, header| {
+        params
+            .iter()
+            .find(|(name, _)| *name == param)
+            .and_then(|(_, value)| urlencoding::decode(value).ok())
+            .map(|value| value.into_owned())
+            .or_else(|| {
+                req.headers()
+                    .get(header)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            })
+            .unwrap_or_default()
+    };
+
+    let preset =
+        Preset::from_name(value("clean_preset", "x-clean-preset").trim()).unwrap_or(Preset::Strict);
+    value("keep_categories", "x-keep-categories")
+        .split(',')
+        .filter_map(|name| Category::from_name(name.trim()))
+        .fold(Categories::from(preset), Categories::without)
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum HttpError {
     #[error("Internal Server Error")]
@@ -49,17 +129,33 @@ window.opener = null; window.location.replace("$$URL_ESCAPED$$" + window.locatio
 </noscript>
 </body></html>"#;
 
+const WARNING_HTML: &str = r#"
+<!DOCTYPE html>
+<html><head>
+<title>Warning</title>
+<meta name="referrer" content="no-referrer" />
+</head>
+<body style="background-color: #000;color: #ccc;">
+<h1>This link may not be safe</h1>
+<p><code>$$URL$$</code></p>
+$$DOMAIN$$
+<ul>$$WARNINGS$$</ul>
+$$CONTINUE$$
+</body></html>"#;
+
 #[tracing::instrument(
     skip(req),
     fields(
         cleaned = tracing::field::Empty,
         json = false,
         http.header.ip = tracing::field::Empty,
-        used_handlers = tracing::field::Empty
+        used_handlers = tracing::field::Empty,
+        safe = tracing::field::Empty
     )
 )]
 pub async fn redirect(req: actix_web::HttpRequest) -> impl Responder {
-    let input_url = req.query_string().to_string();
+    let (clean_params, input_url) = split_clean_params(req.query_string());
+    let input_url = input_url.to_string();
     let input_url = urlencoding::decode(&input_url)
         .map(|r| r.to_string())
         .unwrap_or_else(|_| input_url);
@@ -75,7 +171,13 @@ pub async fn redirect(req: actix_web::HttpRequest) -> impl Responder {
     }
 
     if !input_url.is_empty() {
-        if let Ok(result) = tracking_params::clean_str_raw(&input_url) {
+        let options = Options {
+            categories: requested_categories(&req, &clean_params),
+            ..Default::default()
+        };
+        if let Ok(result) =
+            url::Url::parse(&input_url).map(|url| tracking_params::clean_with(url, &options))
+        {
             tracing::Span::current().record("used_handlers", result.number_of_handlers_used());
             let hostname = result.host_str().unwrap_or(&input_url);
             let cleaned = result.to_string();
@@ -90,6 +192,14 @@ pub async fn redirect(req: actix_web::HttpRequest) -> impl Responder {
                     .body("<h1>This URL has been blocked for your safety</h1>");
             }
 
+            let safety = result.safety();
+            let warnings = safety
+                .warnings()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            tracing::Span::current().record("safe", warnings.is_empty());
+
             if let Some(ct) = req.headers().get("content-type") {
                 if ct
                     .as_bytes()
@@ -98,16 +208,27 @@ pub async fn redirect(req: actix_web::HttpRequest) -> impl Responder {
                 {
                     tracing::Span::current().record("json", true);
                     return HttpResponse::Ok()
+                        .append_header(VARY)
                         .append_header(("content-type", "application/json"))
                         .body(
                             json!({
                                 "cleaned_url": cleaned,
-                                "original_url": input_url
+                                "original_url": input_url,
+                                "warnings": warnings,
+                                "categories": options.categories.iter().map(|c| c.name()).collect::<Vec<_>>(),
+                                "domain": safety.idn.as_ref().map(|idn| json!({
+                                    "unicode": idn.unicode,
+                                    "punycode": idn.punycode
+                                }))
                             })
                             .to_string(),
                         );
                 }
             }
+            if !warnings.is_empty() {
+                return unsafe_url_warning(&cleaned, &safety);
+            }
+
             let cleaned_escaped = cleaned.replace('/', r#"\/"#);
             let html = REDIRECT_HTML
                 .replace("$$URL$$", &cleaned)
@@ -115,6 +236,7 @@ pub async fn redirect(req: actix_web::HttpRequest) -> impl Responder {
                 .replace("$$URL_ESCAPED$$", &cleaned_escaped);
             return HttpResponse::Ok()
                 .append_header(("cache-control", "public, max-age=300"))
+                .append_header(VARY)
                 .append_header(("content-type", "text/html; charset=utf-8"))
                 .body(html);
         }
@@ -126,6 +248,60 @@ pub async fn redirect(req: actix_web::HttpRequest) -> impl Responder {
         .finish()
 }
 
+fn unsafe_url_warning(url: &str, safety: &tracking_params::Safety) -> HttpResponse {
+    let refuse = *UNSAFE_URL_POLICY == UnsafeUrlPolicy::Refuse
+        || safety.scheme == tracking_params::SchemeRisk::Dangerous;
+
+    let url = escape_html(url);
+    let warnings = safety
+        .warnings()
+        .iter()
+        .map(|warning| format!("<li>{}</li>", escape_html(&warning.to_string())))
+        .collect::<String>();
+    // The unicode form is what the link pretends to be, the punycode form is where it goes.
+    let domain = safety
+        .idn
+        .as_ref()
+        .map(|idn| {
+            format!(
+                "<table><tr><th>Looks like</th><td><code>{}</code></td></tr><tr><th>Actually is</th><td><code>{}</code></td></tr></table>",
+                escape_html(&idn.unicode),
+                escape_html(&idn.punycode)
+            )
+        })
+        .unwrap_or_default();
+    let continue_link = if refuse {
+        String::new()
+    } else {
+        format!(r#"<p><a href="{url}" rel="noreferrer noopener">Continue anyway</a></p>"#)
+    };
+    let html = WARNING_HTML
+        .replace("$$URL$$", &url)
+        .replace("$$DOMAIN$$", &domain)
+        .replace("$$WARNINGS$$", &warnings)
+        .replace("$$CONTINUE$$", &continue_link);
+
+    let mut response = if refuse {
+        HttpResponse::Forbidden()
+    } else {
+        HttpResponse::Ok()
+    };
+    response
+        .append_header(("cache-control", "public, max-age=300"))
+        .append_header(VARY)
+        .append_header(("content-type", "text/html; charset=utf-8"))
+        .body(html)
+}
+
+fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
 pub async fn allowed_list(_req: actix_web::HttpRequest) -> impl Responder {
     HttpResponse::Ok()
         .append_header(("cache-control", "public, max-age=300"))